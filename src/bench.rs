@@ -1,34 +1,43 @@
 use crate::{
-    bar, config::QueryConfig, error::Error, metrics_sender::MetricsSender,
-    metrics_storage::MetricsStorage, requester::Requester,
+    bar,
+    config::{Query, QueryConfig},
+    error::Error,
+    metrics_sink::{self, MetricsSink, RunInfo, RunStatus},
+    prometheus,
+    requester::Requester,
 };
 use bar::OptionalBar;
-use chrono::Duration;
+use chrono::{Duration, Utc};
 use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::{collections::HashMap, env, fs};
 
 pub struct Bench {
     opts: crate::BenchOpt,
     query_config: QueryConfig,
-    metrics_sender: MetricsSender,
-    metrics_storage: MetricsStorage,
+    sinks: Vec<Box<dyn MetricsSink>>,
     spinner: ProgressStyle,
-    requester: Requester,
+    requesters: Vec<Requester>,
 }
 
 impl Bench {
     pub async fn new(opts: crate::BenchOpt) -> crate::Result<Self> {
-        let requester = Requester::new(opts.endpoint_type, opts.endpoint_url.clone())?;
-        let query_config = QueryConfig::new(&opts.query_file)?;
-
-        let metrics_sender = MetricsSender::new(
-            query_config.elastic_endpoint(),
-            &opts.metrics_database,
-            &opts.elastic_user,
-            &opts.elastic_password,
-        );
+        let query_config = QueryConfig::new(&opts.query_file).await?;
+
+        let requesters = if query_config.endpoints().is_empty() {
+            vec![Requester::new(
+                opts.endpoint_type,
+                opts.endpoint_url.clone(),
+            )?]
+        } else {
+            query_config
+                .endpoints()
+                .iter()
+                .map(Requester::from_endpoint)
+                .collect::<crate::Result<Vec<_>>>()?
+        };
 
-        let metrics_storage = MetricsStorage::new(&opts.secondary_storage).await?;
+        let sinks = metrics_sink::from_config(&opts, &query_config).await?;
 
         let spinner = ProgressStyle::default_spinner()
             .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
@@ -37,82 +46,214 @@ impl Bench {
         Ok(Self {
             opts,
             query_config,
-            metrics_sender,
-            metrics_storage,
+            sinks,
             spinner,
-            requester,
+            requesters,
         })
     }
 
     pub async fn run(&mut self) -> crate::Result<()> {
-        let info = self.requester.server_info().await?;
+        for sink in self.sinks.iter() {
+            match sink.verify().await {
+                Err(e) if self.opts.skip_sink_verification => {
+                    println!("{} {}", style("Warning:").yellow().bold(), e)
+                }
+                result => result?,
+            }
+        }
+
+        for requester in self.requesters.iter() {
+            let info = requester.server_info().await?;
+            requester.label_server(&info);
 
-        if self.metrics_storage.contains(&info).await? {
-            return Err(Error::AlreadyMeasured {
-                commit_id: info.commit,
-                connector: info.primary_connector,
-            });
+            for sink in self.sinks.iter() {
+                if !self.opts.allow_remeasure && sink.contains(&info).await? {
+                    return Err(Error::AlreadyMeasured {
+                        commit_id: info.commit,
+                        connector: info.primary_connector,
+                    });
+                }
+            }
+
+            println!(
+                "Server info{} :: commit: {}, version: {}, primary_connector: {}",
+                requester
+                    .name()
+                    .map(|name| format!(" ({})", name))
+                    .unwrap_or_default(),
+                style(&format!("{}", info.commit)).bold(),
+                style(&format!("{}", info.version)).bold(),
+                style(&format!("{}", info.primary_connector)).bold(),
+            );
         }
 
-        println!(
-            "Server info :: commit: {}, version: {}, primary_connector: {}",
-            style(&format!("{}", info.commit)).bold(),
-            style(&format!("{}", info.version)).bold(),
-            style(&format!("{}", info.primary_connector)).bold(),
-        );
+        if let Some(addr) = self.opts.metrics_listen {
+            let targets = self.requesters.iter().map(Requester::live_target).collect();
+            prometheus::serve(addr, targets)?;
+        }
 
         if self.opts.validate {
             self.validate().await?;
         }
 
-        let tests = self.query_config.test_count();
-        let total_tests = self.query_config.test_count();
+        let repeat = self.opts.repeat.max(1);
+
+        for repetition in 1..=repeat {
+            if repeat > 1 {
+                println!(
+                    "Repetition {}",
+                    style(&format!("{}/{}", repetition, repeat)).bold()
+                );
+            }
+
+            let mut info = self.run_info()?;
+
+            // Sinks without ids of their own, like files, get the one of
+            // the storage, which comes first.
+            for sink in self.sinks.iter() {
+                if let Some(id) = sink.start_run(&info).await? {
+                    println!("Storing results as run {}", id);
+                    info.id = info.id.or(Some(id));
+                }
+            }
+
+            let result = self.run_queries().await;
+
+            let status = match result {
+                Ok(()) => RunStatus::Completed,
+                Err(_) => RunStatus::Failed,
+            };
+
+            for sink in self.sinks.iter() {
+                sink.finish_run(status).await?;
+            }
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_queries(&mut self) -> crate::Result<()> {
+        let runs = Self::runs(&self.query_config, &self.requesters);
+        let total_tests = runs.len();
 
         let total_time = Duration::seconds(
-            (self.query_config.duration().as_secs() * (total_tests as u64)) as i64,
+            runs.iter()
+                .map(|(_, query, _, _)| query.duration().as_secs() as i64)
+                .sum(),
         );
 
         let total_hours = total_time.num_hours();
         let total_minutes = total_time.num_minutes() - total_hours * 60;
 
         println!(
-            "Running {} tests, {} seconds for each. Ready in about {} and {}...",
+            "Running {} tests, {} seconds for each by default. Ready in about {} and {}...",
             style(&format!("{}", total_tests)).bold(),
             style(&format!("{}", self.query_config.duration().as_secs())).bold(),
             style(&format!("{} hour(s)", total_hours)).bold(),
             style(&format!("{} minute(s)", total_minutes)).bold(),
         );
 
-        for (i, (query, rps)) in self.query_config.runs().enumerate() {
+        for (i, (requester_index, query, rps, batch)) in runs.into_iter().enumerate() {
+            let requester = &mut self.requesters[requester_index];
+
             let pb = if self.opts.show_progress {
-                OptionalBar::from(ProgressBar::new(self.query_config.duration().as_secs()))
+                OptionalBar::from(ProgressBar::new(query.duration().as_secs()))
             } else {
                 OptionalBar::empty()
             };
 
             println!(
-                "[{}] {} ({} rps)",
-                style(&format!("{}/{}", i + 1, tests)).bold().dim(),
+                "[{}] {} ({} rps{}){}",
+                style(&format!("{}/{}", i + 1, total_tests)).bold().dim(),
                 query.name(),
                 rps,
+                batch
+                    .map(|batch| format!(", batch of {}", batch))
+                    .unwrap_or_default(),
+                requester
+                    .name()
+                    .map(|name| format!(" @ {}", style(name).bold()))
+                    .unwrap_or_default(),
             );
 
             pb.set_style(self.spinner.clone());
 
-            self.requester
-                .run(&query, rps, self.query_config.duration(), &pb)
-                .await;
+            for hook in query.setup() {
+                hook.run("setup", requester, query.single_query()).await?;
+            }
+
+            requester
+                .run(query, rps, batch, query.duration(), &pb)
+                .await?;
+
+            let metrics = requester.json_metrics(query.name(), rps, batch).await?;
+            let series = requester.series_metrics(query.name(), rps, batch).await?;
+
+            for sink in self.sinks.iter() {
+                sink.store(&metrics).await?;
+                sink.store_series(&series).await?;
+            }
 
-            let metrics = self.requester.json_metrics(query.name(), rps).await?;
-            self.metrics_sender.send(&metrics).await?;
-            self.metrics_storage.store(&metrics).await?;
+            for hook in query.teardown() {
+                hook.run("teardown", requester, query.single_query())
+                    .await?;
+            }
 
-            println!("{}", self.requester.console_metrics());
+            println!("{}", requester.console_metrics());
         }
 
         Ok(())
     }
 
+    fn run_info(&self) -> crate::Result<RunInfo> {
+        let join = |f: &dyn Fn(&Requester) -> String| {
+            self.requesters.iter().map(f).collect::<Vec<_>>().join(", ")
+        };
+
+        Ok(RunInfo {
+            id: None,
+            identifier: self.query_config.identifier().into(),
+            config: self.query_config.source().into(),
+            endpoint_url: join(&|r| r.endpoint_url().into()),
+            endpoint_type: join(&|r| r.endpoint_type().as_str().into()),
+            host: hostname(),
+            duration: self.query_config.duration().as_secs(),
+            started_at: Utc::now(),
+        })
+    }
+
+    /// All (endpoint, query, rps, batch size) runs. With several endpoints, the same query
+    /// and rate runs on each endpoint right after another, so environment
+    /// changes during the benchmark hit every endpoint alike.
+    fn runs<'a>(
+        query_config: &'a QueryConfig,
+        requesters: &[Requester],
+    ) -> Vec<(usize, &'a Query, u64, Option<u64>)> {
+        let mut groups: Vec<Vec<(usize, &Query, u64, Option<u64>)>> = Vec::new();
+        let mut group_index: HashMap<(&str, u64, Option<u64>), usize> = HashMap::new();
+
+        for (query, rps, batch) in query_config.runs() {
+            for (i, requester) in requesters.iter().enumerate() {
+                if !query.single_query().runs_on(requester.name()) {
+                    continue;
+                }
+
+                let index = *group_index
+                    .entry((query.name(), rps, batch))
+                    .or_insert_with(|| {
+                        groups.push(Vec::new());
+                        groups.len() - 1
+                    });
+
+                groups[index].push((i, query, rps, batch));
+            }
+        }
+
+        groups.into_iter().flatten().collect()
+    }
+
     async fn validate(&self) -> crate::Result<()> {
         let show_progress = self.opts.show_progress;
         let pb = if show_progress {
@@ -122,8 +263,22 @@ impl Bench {
         };
 
         println!("Validating queries...");
-        self.requester.validate(&self.query_config, pb).await?;
+
+        for requester in self.requesters.iter() {
+            requester.validate(&self.query_config, pb.clone()).await?;
+        }
 
         Ok(())
     }
 }
+
+/// The name of the machine running the benchmark, without spawning a
+/// process on the runtime.
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("unknown"))
+}
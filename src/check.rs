@@ -0,0 +1,271 @@
+//! A regression gate for CI: compares the measurements like the reports do,
+//! prints the verdict as a single line of JSON, and exits with a non-zero
+//! status if a query regressed beyond its budget.
+
+use crate::{
+    response_summary::{QueryComparison, ResponseSummary},
+    significance::{Change, Verdict},
+    BudgetOpt, CheckOpt,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, process};
+
+/// The largest allowed regression per percentile, in percent. A percentile
+/// fails its limit when the whole confidence interval of the change lies
+/// above it, also for limits below the noise threshold. A missing limit
+/// fails on any significant regression.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Limits {
+    p50: Option<f64>,
+    p95: Option<f64>,
+    p99: Option<f64>,
+}
+
+impl Limits {
+    /// Takes the limits of `self`, and the ones of `other` where unset.
+    fn or(self, other: Limits) -> Self {
+        Self {
+            p50: self.p50.or(other.p50),
+            p95: self.p95.or(other.p95),
+            p99: self.p99.or(other.p99),
+        }
+    }
+
+    /// The percentiles of the comparison that regressed beyond their
+    /// limit.
+    pub fn violations(&self, comparison: &QueryComparison) -> Vec<Violation> {
+        let percentiles = [
+            ("p50", comparison.p50, self.p50),
+            ("p95", comparison.p95, self.p95),
+            ("p99", comparison.p99, self.p99),
+        ];
+
+        percentiles
+            .iter()
+            .filter(|(_, change, limit)| match limit {
+                Some(limit) => change.low > *limit,
+                None => change.verdict == Verdict::Regressed,
+            })
+            .map(|(percentile, change, limit)| Violation {
+                percentile: *percentile,
+                change: *change,
+                limit: *limit,
+            })
+            .collect()
+    }
+}
+
+/// A percentile of a query that regressed beyond its limit.
+#[derive(Debug, Clone, Copy)]
+pub struct Violation {
+    pub percentile: &'static str,
+    pub change: Change,
+    pub limit: Option<f64>,
+}
+
+/// The budget file, with limits for all queries and overrides per query
+/// name:
+///
+/// ```toml
+/// [default]
+/// p95 = 10.0
+///
+/// [query.find_many_users]
+/// p50 = 5.0
+/// p99 = 20.0
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Budget {
+    #[serde(default)]
+    default: Limits,
+    #[serde(default)]
+    query: HashMap<String, Limits>,
+    /// The limits given on the command line.
+    #[serde(skip)]
+    cli: Limits,
+}
+
+impl Budget {
+    /// The budget file of the options, if any, with the limits of the
+    /// command line.
+    pub fn from_opts(opts: &BudgetOpt) -> crate::Result<Self> {
+        let mut budget: Self = match opts.budget {
+            Some(ref path) => toml::from_str(&fs::read_to_string(path)?)?,
+            None => Self::default(),
+        };
+
+        budget.cli = Limits {
+            p50: opts.p50,
+            p95: opts.p95,
+            p99: opts.p99,
+        };
+
+        Ok(budget)
+    }
+
+    /// The limits of the query. The budget entry of the query wins over
+    /// the command line, which wins over the budget defaults.
+    pub fn limits(&self, query_name: &str) -> Limits {
+        self.query
+            .get(query_name)
+            .copied()
+            .unwrap_or_default()
+            .or(self.cli)
+            .or(self.default)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Regression {
+    query: String,
+    query_name: String,
+    percentile: &'static str,
+    change: f64,
+    low: f64,
+    high: f64,
+    limit: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckResult<'a> {
+    verdict: &'static str,
+    connector: &'a str,
+    base: &'a str,
+    head: &'a str,
+    regressions: Vec<Regression>,
+    /// Queries regressed within their budget.
+    tolerated: Vec<String>,
+    improved: Vec<String>,
+    /// Queries without measurements in the base to compare with.
+    new: Vec<String>,
+}
+
+pub async fn check(opts: CheckOpt) -> crate::Result<()> {
+    let budget = Budget::from_opts(&opts.budget)?;
+
+    let summary = ResponseSummary::aggregate(
+        &opts.secondary_storage,
+        opts.connector,
+        &opts.selection.selection(),
+    )
+    .await?;
+
+    let (base, head) = summary.commits();
+
+    let mut result = CheckResult {
+        verdict: "pass",
+        connector: opts.connector.as_str(),
+        base,
+        head,
+        regressions: Vec::new(),
+        tolerated: Vec::new(),
+        improved: Vec::new(),
+        new: Vec::new(),
+    };
+
+    for (samples, comparison) in summary.differences() {
+        let comparison = match comparison {
+            Some(comparison) => comparison,
+            None => {
+                result.new.push(samples.label());
+                continue;
+            }
+        };
+
+        let violations = budget.limits(samples.query_name()).violations(&comparison);
+
+        match comparison.verdict {
+            Verdict::Regressed if violations.is_empty() => result.tolerated.push(samples.label()),
+            Verdict::Improved => result.improved.push(samples.label()),
+            _ => (),
+        }
+
+        result
+            .regressions
+            .extend(violations.into_iter().map(|violation| Regression {
+                query: samples.label(),
+                query_name: samples.query_name().into(),
+                percentile: violation.percentile,
+                change: violation.change.change,
+                low: violation.change.low,
+                high: violation.change.high,
+                limit: violation.limit,
+            }));
+    }
+
+    if !result.regressions.is_empty() {
+        result.verdict = "fail";
+    }
+
+    println!("{}", serde_json::to_string(&result)?);
+
+    if result.regressions.is_empty() {
+        Ok(())
+    } else {
+        process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A comparison where every percentile changed the same, with the given
+    /// interval, and a noise threshold of 5%.
+    fn comparison(change: f64, low: f64, high: f64) -> QueryComparison {
+        let change = Change {
+            change,
+            low,
+            high,
+            verdict: if low > 5.0 {
+                Verdict::Regressed
+            } else {
+                Verdict::Inconclusive
+            },
+        };
+
+        QueryComparison {
+            p50: change,
+            p95: change,
+            p99: change,
+            verdict: change.verdict,
+        }
+    }
+
+    fn p95(limit: f64) -> Limits {
+        Limits {
+            p95: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn limit_below_the_noise_threshold_catches_small_regressions() {
+        let violations = p95(3.0).violations(&comparison(4.0, 3.5, 4.5));
+
+        assert_eq!(1, violations.len());
+        assert_eq!("p95", violations[0].percentile);
+    }
+
+    #[test]
+    fn limit_needs_the_whole_interval_above_it() {
+        assert!(p95(3.0).violations(&comparison(4.0, 2.5, 5.5)).is_empty());
+    }
+
+    #[test]
+    fn regression_within_the_limit_is_tolerated() {
+        assert!(p95(20.0)
+            .violations(&comparison(10.0, 8.0, 12.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn without_a_limit_any_significant_regression_fails() {
+        let limits = Limits::default();
+
+        assert_eq!(3, limits.violations(&comparison(10.0, 8.0, 12.0)).len());
+        assert!(limits.violations(&comparison(4.0, 3.5, 4.5)).is_empty());
+    }
+}
@@ -1,14 +1,27 @@
+mod document;
+mod endpoint;
+mod graphql;
 mod query;
+mod sink;
+mod source;
+mod variable;
 
+pub use document::*;
+pub use endpoint::*;
 pub use query::*;
+pub use sink::*;
+pub use variable::*;
 
+use crate::{error::Error, hook::Hook};
+use graphql::Definition;
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
-    fs::File,
+    fs::{self, File},
     io::Read,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use walkdir::WalkDir;
 
@@ -18,15 +31,48 @@ static MEDIUM_RATES: &[u64] = &[100, 200, 300, 400, 500, 600, 700, 800, 900, 100
 static FAST_RATES: &[u64] = &[200, 400, 600, 800, 1000, 1200, 1400, 1600, 1800, 2000, 4000];
 static VERY_FAST_RATES: &[u64] = &[200, 400, 600, 800, 1000, 1200, 1400, 1600, 1800, 2000, 4000];
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub(super) struct TestRun {
     path: PathBuf,
     #[serde(default = "HashMap::new")]
     variables: HashMap<String, QueryVariable>,
-    batch: Option<u64>,
+    batch: Option<BatchSizes>,
+    #[serde(default)]
+    setup: Vec<Hook>,
+    #[serde(default)]
+    teardown: Vec<Hook>,
+    /// Overrides `duration_per_test` for the queries of this run.
+    duration_per_test: Option<u64>,
+    /// Overrides single speeds of the global rates.
+    rates: Option<RatesConfig>,
+    /// Sends the queries of this run to another URL.
+    endpoint_url: Option<String>,
+    /// Extra headers, on top of the global ones.
+    #[serde(default = "HashMap::new")]
+    headers: HashMap<String, String>,
+    /// Runs the queries only against the endpoint of this name, instead of
+    /// every endpoint.
+    endpoint: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Either one batch size, or a list of sizes to sweep.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(super) enum BatchSizes {
+    Single(u64),
+    Sweep(Vec<u64>),
+}
+
+impl BatchSizes {
+    fn to_vec(&self) -> Vec<u64> {
+        match self {
+            Self::Single(batch) => vec![*batch],
+            Self::Sweep(batches) => batches.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
 pub(super) struct RatesConfig {
     very_slow: Option<Vec<u64>>,
     slow: Option<Vec<u64>>,
@@ -38,25 +84,125 @@ pub(super) struct RatesConfig {
 #[derive(Deserialize, Debug)]
 pub(super) struct TestConfig {
     identifier: String,
-    elastic_endpoint: String,
+    elastic_endpoint: Option<String>,
     duration_per_test: u64,
+    #[serde(default)]
     test_run: Vec<TestRun>,
+    #[serde(default)]
     rates: RatesConfig,
+    seed: Option<u64>,
+    /// Variables shared by every test run.
+    #[serde(default = "HashMap::new")]
+    variables: HashMap<String, QueryVariable>,
+    /// Headers sent with every request.
+    #[serde(default = "HashMap::new")]
+    headers: HashMap<String, String>,
+    /// Named endpoints to compare. Without any, the endpoint from the
+    /// command line is used.
+    #[serde(default = "Vec::new")]
+    endpoint: Vec<EndpointConfig>,
+    /// Where to store the results. Without this, the results go to the
+    /// secondary storage and, if configured, to Elastic Search.
+    sink: Option<Vec<SinkConfig>>,
+    /// The merged configuration, for the run records.
+    #[serde(skip)]
+    source: String,
 }
 
 impl TryFrom<&str> for TestConfig {
     type Error = crate::error::Error;
 
     fn try_from(path: &str) -> crate::Result<Self> {
-        let mut f = File::open(path)?;
+        let (value, merged) = source::load(Path::new(path))?;
+        let mut config: Self = value.try_into()?;
+        config.source = merged;
+
+        check_headers(&config.headers)?;
+
+        for endpoint in config.endpoint.iter() {
+            check_url(endpoint.url())?;
+            check_headers(endpoint.headers())?;
+        }
+
+        for test_run in config.test_run.iter() {
+            if let Some(ref url) = test_run.endpoint_url {
+                if !config.endpoint.is_empty() {
+                    return Err(Error::InvalidConfig(format!(
+                        "test run {} overrides the endpoint_url of the named endpoints",
+                        test_run.path.display(),
+                    )));
+                }
+
+                check_url(url)?;
+            }
+
+            check_headers(&test_run.headers)?;
+
+            if let Some(ref name) = test_run.endpoint {
+                if !config.endpoint.iter().any(|e| e.name() == name) {
+                    return Err(Error::InvalidConfig(format!(
+                        "test run {} refers to an unknown endpoint '{}'",
+                        test_run.path.display(),
+                        name
+                    )));
+                }
+            }
+        }
+
+        let endpoint_runs: Vec<TestRun> = config
+            .endpoint
+            .iter()
+            .filter_map(|endpoint| {
+                endpoint.path().map(|path| TestRun {
+                    path: path.clone(),
+                    endpoint: Some(endpoint.name().into()),
+                    ..Default::default()
+                })
+            })
+            .collect();
 
-        let mut config_str = String::new();
-        f.read_to_string(&mut config_str)?;
+        config.test_run.extend(endpoint_runs);
 
-        Ok(toml::from_str(&config_str)?)
+        Ok(config)
     }
 }
 
+/// Fails if the URL cannot be requested, instead of panicking on the first
+/// request.
+pub(crate) fn check_url(url: &str) -> crate::Result<()> {
+    let uri: http::Uri = url
+        .parse()
+        .map_err(|e| Error::InvalidConfig(format!("invalid URL '{}': {}", url, e)))?;
+
+    if uri.scheme().is_none() || uri.host().is_none() {
+        return Err(Error::InvalidConfig(format!(
+            "invalid URL '{}': scheme and host are required",
+            url
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_headers(headers: &HashMap<String, String>) -> crate::Result<()> {
+    for (name, value) in headers.iter() {
+        http::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| Error::InvalidConfig(format!("invalid header name '{}': {}", name, e)))?;
+
+        http::header::HeaderValue::from_str(value).map_err(|e| {
+            Error::InvalidConfig(format!("invalid value of header '{}': {}", name, e))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The sinks declared in a test file, without loading its queries.
+pub fn sink_configs(path: &str) -> crate::Result<Vec<SinkConfig>> {
+    let mut config = TestConfig::try_from(path)?;
+    Ok(config.take_sinks().unwrap_or_default())
+}
+
 impl TestConfig {
     fn parse_name(path: &Path) -> String {
         path.file_stem()
@@ -65,32 +211,24 @@ impl TestConfig {
             .unwrap()
     }
 
-    fn rps(&self, name: &str) -> Vec<u64> {
+    fn rps(&self, name: &str, overrides: Option<&RatesConfig>) -> Vec<u64> {
+        let pick = |select: fn(&RatesConfig) -> &Option<Vec<u64>>, default: &[u64]| {
+            overrides
+                .and_then(|rates| select(rates).clone())
+                .or_else(|| select(&self.rates).clone())
+                .unwrap_or_else(|| default.to_vec())
+        };
+
         if name.contains("very-slow") {
-            self.rates
-                .very_slow
-                .clone()
-                .unwrap_or_else(|| VERY_SLOW_RATES.to_vec())
+            pick(|r| &r.very_slow, VERY_SLOW_RATES)
         } else if name.contains("slow") {
-            self.rates
-                .slow
-                .clone()
-                .unwrap_or_else(|| SLOW_RATES.to_vec())
+            pick(|r| &r.slow, SLOW_RATES)
         } else if name.contains("medium") {
-            self.rates
-                .medium
-                .clone()
-                .unwrap_or_else(|| MEDIUM_RATES.to_vec())
+            pick(|r| &r.medium, MEDIUM_RATES)
         } else if name.contains("fast") {
-            self.rates
-                .fast
-                .clone()
-                .unwrap_or_else(|| FAST_RATES.to_vec())
+            pick(|r| &r.fast, FAST_RATES)
         } else if name.contains("very-fast") {
-            self.rates
-                .very_fast
-                .clone()
-                .unwrap_or_else(|| VERY_FAST_RATES.to_vec())
+            pick(|r| &r.very_fast, VERY_FAST_RATES)
         } else {
             panic!(
                 "File name should contain the query speed: (very-slow|slow|medium|fast|very-fast)"
@@ -98,60 +236,72 @@ impl TestConfig {
         }
     }
 
+    /// Fetches the values for variables sourced from a database.
+    pub(super) async fn load_variables(&mut self) -> crate::Result<()> {
+        for variable in self.variables.values_mut() {
+            variable.load().await?;
+        }
+
+        for test_run in self.test_run.iter_mut() {
+            for variable in test_run.variables.values_mut() {
+                variable.load().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn take_sinks(&mut self) -> Option<Vec<SinkConfig>> {
+        self.sink.take()
+    }
+
+    pub(super) fn take_endpoints(&mut self) -> Vec<EndpointConfig> {
+        std::mem::replace(&mut self.endpoint, Vec::new())
+    }
+
     pub(super) fn take_queries(&mut self) -> crate::Result<Vec<Query>> {
         let mut queries = Vec::new();
+        let mut fragments = HashMap::new();
+
+        while let Some(mut test_run) = self.test_run.pop() {
+            for (name, variable) in self.variables.iter() {
+                if !test_run.variables.contains_key(name) {
+                    test_run.variables.insert(name.clone(), variable.clone());
+                }
+            }
+
+            if let Some(seed) = self.seed {
+                for (name, variable) in test_run.variables.iter_mut() {
+                    variable.seed_from(seed, name);
+                }
+            }
+
+            let mut paths = Vec::new();
 
-        while let Some(test_run) = self.test_run.pop() {
             if test_run.path.is_dir() {
                 for entry in WalkDir::new(&test_run.path) {
                     let entry = entry?;
                     let path = entry.path();
 
                     match path.extension().and_then(|s| s.to_str()) {
-                        Some("graphql") | Some("json") => {
-                            let mut f = File::open(&path)?;
-                            let mut query = String::new();
-                            f.read_to_string(&mut query)?;
-
-                            let name = Self::parse_name(path);
-                            let rps = self.rps(&name);
-
-                            let query = SingleQuery {
-                                name,
-                                query,
-                                rps,
-                                variables: test_run.variables.clone(),
-                            };
-
-                            match test_run.batch {
-                                Some(batch) => queries.push(Query::Batch { query, batch }),
-                                None => {
-                                    queries.push(Query::Single(query));
-                                }
-                            }
-                        }
+                        Some("graphql") | Some("json") => paths.push(path.to_path_buf()),
                         _ => (),
                     }
                 }
             } else {
-                let mut f = File::open(&test_run.path)?;
-                let mut query = String::new();
-                f.read_to_string(&mut query)?;
-
-                let name = Self::parse_name(&test_run.path);
-                let rps = self.rps(&name);
-
-                let query = SingleQuery {
-                    name,
-                    query,
-                    rps,
-                    variables: test_run.variables,
-                };
-
-                match test_run.batch {
-                    Some(batch) => queries.push(Query::Batch { query, batch }),
-                    None => {
-                        queries.push(Query::Single(query));
+                paths.push(test_run.path.clone());
+            }
+
+            for path in paths {
+                for query in self.file_queries(&test_run, &path, &mut fragments)? {
+                    match test_run.batch {
+                        Some(ref batches) => queries.push(Query::Batch {
+                            query,
+                            batches: batches.to_vec(),
+                        }),
+                        None => {
+                            queries.push(Query::Single(query));
+                        }
                     }
                 }
             }
@@ -159,4 +309,163 @@ impl TestConfig {
 
         Ok(queries)
     }
+
+    /// All queries of one file. A `.graphql` file with several named
+    /// operations is one query per operation, named `<file>::<operation>`.
+    fn file_queries(
+        &self,
+        test_run: &TestRun,
+        path: &Path,
+        fragments: &mut HashMap<PathBuf, HashMap<String, String>>,
+    ) -> crate::Result<Vec<SingleQuery>> {
+        let mut f = File::open(path)?;
+        let mut contents = String::new();
+        f.read_to_string(&mut contents)?;
+
+        let file_name = Self::parse_name(path);
+
+        let documents = match path.extension().and_then(|s| s.to_str()) {
+            Some("json") => vec![(file_name.clone(), QueryDocument::json(&contents)?)],
+            _ => Self::graphql_documents(&file_name, path, &contents, fragments)?,
+        };
+
+        let rps = self.rps(&file_name, test_run.rates.as_ref());
+        let duration = test_run.duration_per_test.unwrap_or(self.duration_per_test);
+
+        let mut headers = self.headers.clone();
+        headers.extend(test_run.headers.clone());
+
+        let queries = documents
+            .into_iter()
+            .map(|(name, document)| SingleQuery {
+                name,
+                document,
+                rps: rps.clone(),
+                variables: test_run.variables.clone(),
+                setup: test_run.setup.clone(),
+                teardown: test_run.teardown.clone(),
+                duration: Duration::from_secs(duration),
+                endpoint_url: test_run.endpoint_url.clone(),
+                headers: headers.clone(),
+                endpoint: test_run.endpoint.clone(),
+            })
+            .collect();
+
+        Ok(queries)
+    }
+
+    fn graphql_documents(
+        file_name: &str,
+        path: &Path,
+        contents: &str,
+        fragments: &mut HashMap<PathBuf, HashMap<String, String>>,
+    ) -> crate::Result<Vec<(String, QueryDocument)>> {
+        let operations: Vec<(Option<String>, String)> = graphql::definitions(contents)
+            .into_iter()
+            .filter_map(|definition| match definition {
+                Definition::Operation { name, text } => Some((name, text)),
+                Definition::Fragment { .. } => None,
+            })
+            .collect();
+
+        // A file of only fragments is not a query by itself.
+        if operations.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        if !fragments.contains_key(&dir) {
+            let found = Self::directory_fragments(&dir)?;
+            fragments.insert(dir.clone(), found);
+        }
+
+        let fragments = &fragments[&dir];
+        let multiple = operations.len() > 1;
+
+        operations
+            .into_iter()
+            .map(|(operation_name, text)| {
+                let query = Self::with_fragments(&text, fragments, path)?;
+
+                match (multiple, operation_name) {
+                    (false, _) => Ok((file_name.to_string(), QueryDocument::graphql(&query))),
+                    (true, Some(operation_name)) => Ok((
+                        format!("{}::{}", file_name, operation_name),
+                        QueryDocument::operation(&query, operation_name),
+                    )),
+                    (true, None) => Err(Error::InvalidConfig(format!(
+                        "{} has several operations, so all of them need a name",
+                        path.display()
+                    ))),
+                }
+            })
+            .collect()
+    }
+
+    /// The fragments of all `.graphql` files in a directory.
+    fn directory_fragments(dir: &Path) -> crate::Result<HashMap<String, String>> {
+        let mut fragments = HashMap::new();
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().and_then(|s| s.to_str()) != Some("graphql") {
+                continue;
+            }
+
+            let contents = fs::read_to_string(&path)?;
+
+            for definition in graphql::definitions(&contents) {
+                if let Definition::Fragment { name, text } = definition {
+                    if fragments.insert(name.clone(), text).is_some() {
+                        return Err(Error::InvalidConfig(format!(
+                            "fragment {} is defined more than once in {}",
+                            name,
+                            dir.display()
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(fragments)
+    }
+
+    /// Appends the fragments an operation uses, directly or through other
+    /// fragments.
+    fn with_fragments(
+        operation: &str,
+        fragments: &HashMap<String, String>,
+        path: &Path,
+    ) -> crate::Result<String> {
+        let mut query = operation.to_string();
+        let mut included = HashSet::new();
+        let mut pending = graphql::fragment_spreads(operation);
+
+        while let Some(name) = pending.pop() {
+            if !included.insert(name.clone()) {
+                continue;
+            }
+
+            let fragment = fragments.get(&name).ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "{} uses the fragment {}, which is not defined in its directory",
+                    path.display(),
+                    name
+                ))
+            })?;
+
+            query.push_str("\n\n");
+            query.push_str(fragment);
+            pending.extend(graphql::fragment_spreads(fragment));
+        }
+
+        Ok(query)
+    }
 }
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// The request sent for a query: the GraphQL operation with its optional
+/// operation name, variables and extensions.
+///
+/// Query variables (`$name`) are resolved in every string of the variables
+/// and extensions. A string holding nothing but a query variable becomes a
+/// JSON number if the variable generates numbers only, such as a range or a
+/// sequence. Only `.graphql` files get them resolved in the query itself,
+/// where GraphQL variable definitions such as `($id: Int!)` are left alone.
+/// Only whole names are replaced, so `$idx` is not taken for `$id`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryDocument {
+    query: String,
+    operation_name: Option<String>,
+    #[serde(default = "empty_object")]
+    variables: Value,
+    extensions: Option<Value>,
+    /// True if query variables are written into the query text.
+    #[serde(skip)]
+    inline_variables: bool,
+}
+
+fn empty_object() -> Value {
+    Value::Object(Map::new())
+}
+
+impl QueryDocument {
+    /// A document from the contents of a `.graphql` file.
+    pub fn graphql(query: &str) -> Self {
+        Self {
+            query: query.trim().to_string(),
+            operation_name: None,
+            variables: empty_object(),
+            extensions: None,
+            inline_variables: true,
+        }
+    }
+
+    /// One named operation out of a document with several of them.
+    pub fn operation(query: &str, operation_name: String) -> Self {
+        Self {
+            query: query.trim().to_string(),
+            operation_name: Some(operation_name),
+            variables: empty_object(),
+            extensions: None,
+            inline_variables: true,
+        }
+    }
+
+    /// A document from the contents of a `.json` file. Files without a
+    /// `query` are sent as the query string itself, as before.
+    pub fn json(contents: &str) -> crate::Result<Self> {
+        let value: Value = serde_json::from_str(contents)?;
+
+        match value.get("query") {
+            Some(Value::String(_)) => {
+                let mut document: Self = serde_json::from_value(value)?;
+
+                if document.variables.is_null() {
+                    document.variables = empty_object();
+                }
+
+                Ok(document)
+            }
+            _ => Ok(Self::graphql(contents)),
+        }
+    }
+
+    /// The request payload with the given variable values filled in.
+    pub fn resolve(&self, values: &HashMap<String, Value>) -> Value {
+        let query = if self.inline_variables {
+            replace(&self.query, values, true)
+        } else {
+            self.query.clone()
+        };
+
+        let mut payload = json!({
+            "query": query,
+            "variables": resolve_value(&self.variables, values),
+        });
+
+        if let Some(ref operation_name) = self.operation_name {
+            payload["operationName"] = Value::String(operation_name.clone());
+        }
+
+        if let Some(ref extensions) = self.extensions {
+            payload["extensions"] = resolve_value(extensions, values);
+        }
+
+        payload
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Replaces every `$name` with a generated value, matching whole names only.
+/// In a query, `$name` followed by a colon is a variable definition and
+/// kept as it is.
+fn replace(text: &str, values: &HashMap<String, Value>, in_query: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+
+        let after = &rest[start + 1..];
+        let end = after
+            .find(|c| !is_name_char(c))
+            .unwrap_or_else(|| after.len());
+        let name = &after[..end];
+
+        let definition = in_query && after[end..].trim_start().starts_with(':');
+
+        match values.get(name) {
+            Some(Value::String(value)) if !name.is_empty() && !definition => result.push_str(value),
+            Some(value) if !name.is_empty() && !definition => result.push_str(&value.to_string()),
+            _ => {
+                result.push('$');
+                result.push_str(name);
+            }
+        }
+
+        rest = &after[end..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn resolve_value(value: &Value, values: &HashMap<String, Value>) -> Value {
+    match value {
+        Value::String(s) => {
+            let whole = Some(s)
+                .filter(|s| s.starts_with('$'))
+                .and_then(|s| values.get(&s[1..]))
+                .cloned();
+
+            whole.unwrap_or_else(|| Value::String(replace(s, values, false)))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| resolve_value(item, values))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, item)| (key.clone(), resolve_value(item, values)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
@@ -0,0 +1,40 @@
+use crate::requester::EndpointType;
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf};
+
+/// A named GraphQL endpoint, for comparing several servers in one
+/// benchmark. Declared with `[[endpoint]]`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EndpointConfig {
+    name: String,
+    #[serde(rename = "type", default)]
+    endpoint_type: EndpointType,
+    url: String,
+    #[serde(default = "HashMap::new")]
+    headers: HashMap<String, String>,
+    /// Queries run only on this endpoint, e.g. when it needs differently
+    /// written queries. Same as a test run bound to the endpoint.
+    path: Option<PathBuf>,
+}
+
+impl EndpointConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn endpoint_type(&self) -> EndpointType {
+        self.endpoint_type
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+}
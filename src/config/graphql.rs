@@ -0,0 +1,167 @@
+//! Just enough GraphQL lexing to split a document into its top-level
+//! operations and fragments, and to find the fragments an operation uses.
+
+/// A top-level definition of a GraphQL document, with its source text.
+#[derive(Debug, Clone)]
+pub(super) enum Definition {
+    Operation { name: Option<String>, text: String },
+    Fragment { name: String, text: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind<'a> {
+    Name(&'a str),
+    Punct(u8),
+    Spread,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    start: usize,
+    end: usize,
+}
+
+/// Tokenizes the document, skipping whitespace, commas and comments.
+fn tokens(source: &str) -> Vec<Token<'_>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+
+        let kind = match bytes[i] {
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+
+                continue;
+            }
+            b' ' | b'\t' | b'\n' | b'\r' | b',' => {
+                i += 1;
+                continue;
+            }
+            b'"' if source[i..].starts_with("\"\"\"") => {
+                i = source[i + 3..]
+                    .find("\"\"\"")
+                    .map(|end| i + 3 + end + 3)
+                    .unwrap_or_else(|| bytes.len());
+
+                TokenKind::Literal
+            }
+            b'"' => {
+                i += 1;
+
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+
+                    i += 1;
+                }
+
+                i += 1;
+                TokenKind::Literal
+            }
+            b'.' if source[i..].starts_with("...") => {
+                i += 3;
+                TokenKind::Spread
+            }
+            b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
+                while i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+
+                TokenKind::Name(&source[start..i])
+            }
+            c => {
+                i += 1;
+                TokenKind::Punct(c)
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            start,
+            end: i.min(bytes.len()),
+        });
+    }
+
+    tokens
+}
+
+/// Splits a document into its operations and fragments.
+pub(super) fn definitions(source: &str) -> Vec<Definition> {
+    let tokens = tokens(source);
+    let mut definitions = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let start = tokens[i].start;
+        let header_start = i;
+
+        while i < tokens.len() && tokens[i].kind != TokenKind::Punct(b'{') {
+            i += 1;
+        }
+
+        let header = &tokens[header_start..i];
+        let mut depth = 0;
+        let mut end = start;
+
+        while i < tokens.len() {
+            match tokens[i].kind {
+                TokenKind::Punct(b'{') => depth += 1,
+                TokenKind::Punct(b'}') => depth -= 1,
+                _ => (),
+            }
+
+            end = tokens[i].end;
+            i += 1;
+
+            if depth == 0 {
+                break;
+            }
+        }
+
+        let text = source[start..end].trim().to_string();
+        let name_after_keyword = match header.get(1).map(|t| t.kind) {
+            Some(TokenKind::Name(name)) => Some(name.to_string()),
+            _ => None,
+        };
+
+        match header.first().map(|t| t.kind) {
+            None => definitions.push(Definition::Operation { name: None, text }),
+            Some(TokenKind::Name("fragment")) => {
+                if let Some(name) = name_after_keyword {
+                    definitions.push(Definition::Fragment { name, text });
+                }
+            }
+            Some(TokenKind::Name("query"))
+            | Some(TokenKind::Name("mutation"))
+            | Some(TokenKind::Name("subscription")) => definitions.push(Definition::Operation {
+                name: name_after_keyword,
+                text,
+            }),
+            _ => (),
+        }
+    }
+
+    definitions
+}
+
+/// The names of the fragments spread in the given definition, not counting
+/// inline fragments.
+pub(super) fn fragment_spreads(text: &str) -> Vec<String> {
+    let tokens = tokens(text);
+
+    tokens
+        .windows(2)
+        .filter_map(|pair| match (pair[0].kind, pair[1].kind) {
+            (TokenKind::Spread, TokenKind::Name(name)) if name != "on" => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,40 +1,77 @@
-use super::TestConfig;
-use rand::Rng;
-use serde::Deserialize;
+use super::{EndpointConfig, QueryDocument, QueryVariable, SinkConfig, TestConfig};
+use crate::hook::Hook;
 use std::{collections::HashMap, convert::TryFrom, time::Duration};
 
 #[derive(Debug)]
 pub struct QueryConfig {
     pub(super) queries: Vec<Query>,
+    pub(super) endpoints: Vec<EndpointConfig>,
     pub(super) duration: Duration,
     pub(super) identifier: String,
-    pub(super) elastic_endpoint: String,
-}
-
-#[derive(Deserialize, Debug, Clone, Copy)]
-pub struct QueryVariable {
-    pub(super) minimum: u64,
-    pub(super) maximum: u64,
+    pub(super) elastic_endpoint: Option<String>,
+    pub(super) sinks: Option<Vec<SinkConfig>>,
+    pub(super) source: String,
 }
 
 #[derive(Debug)]
 pub enum Query {
     Single(SingleQuery),
-    Batch { query: SingleQuery, batch: u64 },
+    Batch {
+        query: SingleQuery,
+        batches: Vec<u64>,
+    },
 }
 
 impl Query {
     pub fn name(&self) -> &str {
         match self {
             Self::Single(q) => q.name(),
-            Self::Batch { query, batch: _ } => query.name(),
+            Self::Batch { query, batches: _ } => query.name(),
         }
     }
 
     pub fn rps(&self) -> &[u64] {
         match self {
             Self::Single(q) => q.rps(),
-            Self::Batch { query, batch: _ } => query.rps(),
+            Self::Batch { query, batches: _ } => query.rps(),
+        }
+    }
+
+    /// The query sent in a single request, or repeated in a batch.
+    pub fn single_query(&self) -> &SingleQuery {
+        match self {
+            Self::Single(q) => q,
+            Self::Batch { query, batches: _ } => query,
+        }
+    }
+
+    pub fn setup(&self) -> &[Hook] {
+        match self {
+            Self::Single(q) => &q.setup,
+            Self::Batch { query, batches: _ } => &query.setup,
+        }
+    }
+
+    pub fn teardown(&self) -> &[Hook] {
+        match self {
+            Self::Single(q) => &q.teardown,
+            Self::Batch { query, batches: _ } => &query.teardown,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Single(q) => q.duration,
+            Self::Batch { query, batches: _ } => query.duration,
+        }
+    }
+
+    /// The batch sizes to sweep, each run on every rate. Empty for queries
+    /// not sent in batches.
+    pub fn batches(&self) -> &[u64] {
+        match self {
+            Self::Single(_) => &[],
+            Self::Batch { query: _, batches } => batches,
         }
     }
 }
@@ -42,21 +79,27 @@ impl Query {
 #[derive(Debug)]
 pub struct SingleQuery {
     pub(super) name: String,
-    pub(super) query: String,
+    pub(super) document: QueryDocument,
     pub(super) rps: Vec<u64>,
     pub(super) variables: HashMap<String, QueryVariable>,
+    pub(super) setup: Vec<Hook>,
+    pub(super) teardown: Vec<Hook>,
+    pub(super) duration: Duration,
+    pub(super) endpoint_url: Option<String>,
+    pub(super) headers: HashMap<String, String>,
+    pub(super) endpoint: Option<String>,
 }
 
 impl SingleQuery {
-    pub fn query(&self) -> String {
-        let mut rng = rand::thread_rng();
-
-        self.variables
+    /// The request payload, with a fresh value for every variable.
+    pub fn payload(&self) -> serde_json::Value {
+        let values: HashMap<String, serde_json::Value> = self
+            .variables
             .iter()
-            .fold(self.query.clone(), |acc, (name, var)| {
-                let x = rng.gen_range(var.minimum, var.maximum);
-                acc.replace(&format!("${}", name), &format!("{}", x))
-            })
+            .map(|(name, var)| (name.clone(), var.generate_value()))
+            .collect();
+
+        self.document.resolve(&values)
     }
 
     pub fn name(&self) -> &str {
@@ -66,17 +109,38 @@ impl SingleQuery {
     pub fn rps(&self) -> &[u64] {
         self.rps.as_slice()
     }
+
+    pub fn endpoint_url(&self) -> Option<&str> {
+        self.endpoint_url.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// True if the query should be sent to the endpoint of the given name.
+    /// Queries not bound to an endpoint run against all of them.
+    pub fn runs_on(&self, endpoint: Option<&str>) -> bool {
+        match (self.endpoint.as_ref(), endpoint) {
+            (Some(bound), Some(endpoint)) => bound == endpoint,
+            _ => true,
+        }
+    }
 }
 
 impl QueryConfig {
-    pub fn new(test_file: &str) -> crate::Result<Self> {
+    pub async fn new(test_file: &str) -> crate::Result<Self> {
         let mut config = TestConfig::try_from(test_file)?;
+        config.load_variables().await?;
 
         Ok(Self {
+            endpoints: config.take_endpoints(),
+            sinks: config.take_sinks(),
             queries: config.take_queries()?,
             duration: Duration::from_secs(config.duration_per_test),
             identifier: config.identifier,
             elastic_endpoint: config.elastic_endpoint,
+            source: config.source,
         })
     }
 
@@ -84,27 +148,57 @@ impl QueryConfig {
         self.duration
     }
 
-    pub fn elastic_endpoint(&self) -> &str {
-        self.elastic_endpoint.as_str()
+    pub fn endpoints(&self) -> &[EndpointConfig] {
+        &self.endpoints
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// The configuration with its includes merged in, as toml. Environment
+    /// variables are not resolved.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn elastic_endpoint(&self) -> Option<&str> {
+        self.elastic_endpoint.as_ref().map(|s| s.as_str())
+    }
+
+    /// The sinks declared in the configuration, if any.
+    pub fn sinks(&self) -> Option<&[SinkConfig]> {
+        self.sinks.as_ref().map(|s| s.as_slice())
     }
 
     pub fn query_count(&self) -> usize {
         self.queries.len()
     }
 
+    /// The time all runs take together.
+    pub fn total_duration(&self) -> Duration {
+        self.runs().map(|(query, _, _)| query.duration()).sum()
+    }
+
     pub fn test_count(&self) -> usize {
-        self.queries.iter().fold(0, |acc, q| match q {
-            Query::Single(q) => acc + q.rps.len(),
-            Query::Batch { query, batch: _ } => acc + query.rps.len(),
-        })
+        self.runs().count()
     }
 
     pub fn queries(&self) -> impl Iterator<Item = &Query> {
         self.queries.iter()
     }
 
-    pub fn runs(&self) -> impl Iterator<Item = (&Query, u64)> {
-        self.queries()
-            .flat_map(move |q| q.rps().iter().map(move |r| (q, *r)))
+    /// All (query, rps, batch size) combinations to run.
+    pub fn runs(&self) -> impl Iterator<Item = (&Query, u64, Option<u64>)> {
+        self.queries().flat_map(move |q| {
+            let batches: Vec<Option<u64>> = match q {
+                Query::Single(_) => vec![None],
+                Query::Batch { query: _, batches } => batches.iter().map(|b| Some(*b)).collect(),
+            };
+
+            batches
+                .into_iter()
+                .flat_map(move |b| q.rps().iter().map(move |r| (q, *r, b)))
+        })
     }
 }
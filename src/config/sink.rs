@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+/// Where to store the results, declared with `[[sink]]`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// An Elastic Search index. Credentials not given here are taken from the
+    /// command line.
+    Elastic {
+        endpoint: String,
+        index: String,
+        user: Option<String>,
+        password: Option<String>,
+        api_key: Option<String>,
+    },
+    /// A SQL database through quaint, e.g. a local SQLite file.
+    Database { url: String },
+}
@@ -0,0 +1,139 @@
+use crate::error::Error;
+use std::{
+    env,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+/// How deep `include` chains can go before we assume a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Reads a configuration file into a toml value, resolving its `include`
+/// list and interpolating `${ENV_VAR}` in every string.
+///
+/// Included files are merged in order, and the including file overrides
+/// whatever they define. Tables are merged key by key, every other value
+/// (arrays included) is replaced as a whole.
+///
+/// Also returns the merged configuration as toml, with the environment
+/// variables left as written so no secrets end up in the stored runs.
+pub(super) fn load(path: &Path) -> crate::Result<(Value, String)> {
+    let mut value = load_with_includes(path, 0)?;
+    let merged = toml::to_string(&value)?;
+
+    interpolate(&mut value)?;
+
+    Ok((value, merged))
+}
+
+fn load_with_includes(path: &Path, depth: usize) -> crate::Result<Value> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::InvalidConfig(format!(
+            "includes nested deeper than {} levels at {}, is there a cycle?",
+            MAX_INCLUDE_DEPTH,
+            path.display()
+        )));
+    }
+
+    let mut f = File::open(path)?;
+
+    let mut config_str = String::new();
+    f.read_to_string(&mut config_str)?;
+
+    let mut value: Value = toml::from_str(&config_str)?;
+
+    let includes = match value.as_table_mut().and_then(|t| t.remove("include")) {
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                other => Err(Error::InvalidConfig(format!(
+                    "include should be a path, got {}",
+                    other
+                ))),
+            })
+            .collect::<crate::Result<Vec<_>>>()?,
+        Some(other) => {
+            return Err(Error::InvalidConfig(format!(
+                "include should be a path or a list of paths, got {}",
+                other
+            )))
+        }
+        None => Vec::new(),
+    };
+
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut merged = Value::Table(Default::default());
+
+    for include in includes {
+        let include_path: PathBuf = dir.join(include);
+        merge(&mut merged, load_with_includes(&include_path, depth + 1)?);
+    }
+
+    merge(&mut merged, value);
+
+    Ok(merged)
+}
+
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Table(base), Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+fn interpolate(value: &mut Value) -> crate::Result<()> {
+    match value {
+        Value::String(s) => *s = interpolate_str(s)?,
+        Value::Array(values) => {
+            for value in values.iter_mut() {
+                interpolate(value)?;
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                interpolate(value)?;
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Replaces every `${NAME}` with the environment variable `NAME`. Query
+/// variables (`$name`, without braces) are left alone.
+fn interpolate_str(s: &str) -> crate::Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::InvalidConfig(format!("unterminated `${{` in \"{}\"", s)))?;
+
+        let name = &rest[start + 2..start + end];
+        let value = env::var(name).map_err(|_| Error::MissingEnvVar(name.into()))?;
+
+        result.push_str(&rest[..start]);
+        result.push_str(&value);
+
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
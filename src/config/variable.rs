@@ -0,0 +1,842 @@
+use crate::error::Error;
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Duration, NaiveDate, Utc,
+};
+use quaint::{prelude::*, single::Quaint};
+use rand::{
+    distributions::{self, Alphanumeric, Distribution},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use serde::Deserialize;
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// A variable in a query, replacing every `$name` with a generated value.
+///
+/// Declared in `[test_run.variables.<name>]`. Without a `kind`, the
+/// `minimum`/`maximum` pair is read as a uniform integer range excluding the
+/// maximum, as it always was, and a `from_sql` query loads its values from a
+/// database before the run.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "VariableDefinition")]
+pub struct QueryVariable {
+    generator: Generator,
+    /// True if every generated value is a number, to be sent as a JSON
+    /// number.
+    numeric: bool,
+    seed: Option<u64>,
+    rng: Arc<Mutex<StdRng>>,
+    counter: Arc<AtomicI64>,
+}
+
+#[derive(Debug, Clone)]
+enum Generator {
+    /// Includes the maximum, except for the legacy range without a `kind`.
+    Uniform {
+        minimum: i64,
+        maximum: i64,
+        inclusive: bool,
+    },
+    Zipf(Zipf),
+    Normal(Normal),
+    Set(Vec<String>),
+    String {
+        length: usize,
+    },
+    Uuid,
+    Date(DateRange),
+    Sequence {
+        step: i64,
+    },
+    Pool(Vec<String>),
+    Sql(SqlSource),
+}
+
+#[derive(Debug, Clone)]
+struct SqlSource {
+    query: String,
+    url: String,
+    bounds_only: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct VariableDefinition {
+    seed: Option<u64>,
+    #[serde(flatten)]
+    generator: GeneratorDefinition,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum GeneratorDefinition {
+    Tagged(TaggedDefinition),
+    Sql {
+        from_sql: String,
+        url: String,
+        #[serde(default)]
+        bounds_only: bool,
+    },
+    Range {
+        minimum: i64,
+        maximum: i64,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TaggedDefinition {
+    Uniform {
+        minimum: i64,
+        maximum: i64,
+    },
+    Zipf {
+        minimum: i64,
+        maximum: i64,
+        #[serde(default = "default_exponent")]
+        exponent: f64,
+    },
+    Normal {
+        mean: f64,
+        std_dev: f64,
+        minimum: Option<i64>,
+        maximum: Option<i64>,
+    },
+    Set {
+        values: Vec<toml::Value>,
+    },
+    String {
+        length: usize,
+    },
+    Uuid,
+    Date {
+        start: String,
+        end: String,
+        format: Option<String>,
+    },
+    Sequence {
+        #[serde(default = "default_start")]
+        start: i64,
+        #[serde(default = "default_step")]
+        step: i64,
+    },
+    File {
+        path: PathBuf,
+        column: String,
+    },
+}
+
+fn default_exponent() -> f64 {
+    1.0
+}
+
+fn default_start() -> i64 {
+    1
+}
+
+fn default_step() -> i64 {
+    1
+}
+
+impl TryFrom<VariableDefinition> for QueryVariable {
+    type Error = Error;
+
+    fn try_from(definition: VariableDefinition) -> crate::Result<Self> {
+        let mut start = 0;
+        let mut numeric = false;
+
+        let generator = match definition.generator {
+            GeneratorDefinition::Range { minimum, maximum } => {
+                if minimum >= maximum {
+                    return Err(Error::InvalidVariable(format!(
+                        "minimum {} should be smaller than maximum {}, which is excluded",
+                        minimum, maximum
+                    )));
+                }
+
+                Generator::Uniform {
+                    minimum,
+                    maximum,
+                    inclusive: false,
+                }
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Uniform { minimum, maximum }) => {
+                check_range(minimum, maximum)?;
+
+                Generator::Uniform {
+                    minimum,
+                    maximum,
+                    inclusive: true,
+                }
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Zipf {
+                minimum,
+                maximum,
+                exponent,
+            }) => {
+                check_range(minimum, maximum)?;
+
+                if exponent <= 0.0 {
+                    return Err(Error::InvalidVariable(format!(
+                        "zipf exponent must be positive, got {}",
+                        exponent
+                    )));
+                }
+
+                Generator::Zipf(Zipf::new(minimum, maximum, exponent)?)
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Normal {
+                mean,
+                std_dev,
+                minimum,
+                maximum,
+            }) => {
+                if std_dev < 0.0 {
+                    return Err(Error::InvalidVariable(format!(
+                        "normal std_dev must not be negative, got {}",
+                        std_dev
+                    )));
+                }
+
+                Generator::Normal(Normal {
+                    mean,
+                    std_dev,
+                    minimum,
+                    maximum,
+                })
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Set { values }) => {
+                if values.is_empty() {
+                    return Err(Error::InvalidVariable("set has no values".into()));
+                }
+
+                numeric = values
+                    .iter()
+                    .all(|value| value.is_integer() || value.is_float());
+
+                let values = values
+                    .into_iter()
+                    .map(|value| match value {
+                        toml::Value::String(s) => s,
+                        value => value.to_string(),
+                    })
+                    .collect();
+
+                Generator::Set(values)
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::String { length }) => {
+                Generator::String { length }
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Uuid) => Generator::Uuid,
+            GeneratorDefinition::Tagged(TaggedDefinition::Date { start, end, format }) => {
+                Generator::Date(DateRange::new(&start, &end, format)?)
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::Sequence { start: first, step }) => {
+                start = first;
+                Generator::Sequence { step }
+            }
+            GeneratorDefinition::Tagged(TaggedDefinition::File { path, column }) => {
+                Generator::Pool(read_column(&path, &column)?)
+            }
+            GeneratorDefinition::Sql {
+                from_sql,
+                url,
+                bounds_only,
+            } => Generator::Sql(SqlSource {
+                query: from_sql,
+                url,
+                bounds_only,
+            }),
+        };
+
+        let rng = match definition.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        if let Generator::Uniform { .. }
+        | Generator::Zipf(_)
+        | Generator::Normal(_)
+        | Generator::Sequence { .. } = generator
+        {
+            numeric = true;
+        }
+
+        Ok(Self {
+            generator,
+            numeric,
+            seed: definition.seed,
+            rng: Arc::new(Mutex::new(rng)),
+            counter: Arc::new(AtomicI64::new(start)),
+        })
+    }
+}
+
+impl QueryVariable {
+    /// Seeds the variable from the test-wide seed, unless it has its own.
+    pub(super) fn seed_from(&mut self, seed: u64, name: &str) {
+        if self.seed.is_some() {
+            return;
+        }
+
+        // FNV-1a, so every variable gets its own stable stream.
+        let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |acc, b| {
+            (acc ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+        self.seed = Some(seed ^ hash);
+        self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed ^ hash)));
+    }
+
+    /// Loads the values of a `from_sql` variable from the database. Either
+    /// keeps all values as the pool to sample from, or with `bounds_only` a
+    /// uniform range between the smallest and largest value.
+    pub(super) async fn load(&mut self) -> crate::Result<()> {
+        let source = match &self.generator {
+            Generator::Sql(source) => source.clone(),
+            _ => return Ok(()),
+        };
+
+        let db = Quaint::new(&source.url).await?;
+        let result_set = db.query_raw(&source.query, &[]).await?;
+
+        let mut values = Vec::new();
+        let mut numeric = true;
+
+        for row in result_set.into_iter() {
+            if row.is_empty() {
+                return Err(Error::InvalidConfig(format!(
+                    "query `{}` of a variable returned no columns",
+                    source.query
+                )));
+            }
+
+            let value = &row[0];
+
+            let value = match (value.as_i64(), value.as_str(), value.as_f64()) {
+                (Some(i), _, _) => i.to_string(),
+                (_, Some(s), _) => {
+                    numeric = false;
+                    s.to_string()
+                }
+                (_, _, Some(f)) => f.to_string(),
+                _ => continue,
+            };
+
+            values.push(value);
+        }
+
+        if values.is_empty() {
+            return Err(Error::InvalidVariable(format!(
+                "query `{}` returned no values",
+                source.query
+            )));
+        }
+
+        self.generator = if source.bounds_only {
+            let numbers: Vec<i64> = values.iter().filter_map(|v| v.parse().ok()).collect();
+
+            match (numbers.iter().min(), numbers.iter().max()) {
+                (Some(minimum), Some(maximum)) => Generator::Uniform {
+                    minimum: *minimum,
+                    maximum: *maximum,
+                    inclusive: true,
+                },
+                _ => {
+                    return Err(Error::InvalidVariable(format!(
+                        "query `{}` returned no integers to derive bounds from",
+                        source.query
+                    )))
+                }
+            }
+        } else {
+            Generator::Pool(values)
+        };
+
+        self.numeric = source.bounds_only || numeric;
+
+        Ok(())
+    }
+
+    /// A fresh value for a JSON document: a number if the generator only
+    /// gives numbers, a string otherwise, even if it happens to hold digits.
+    pub fn generate_value(&self) -> serde_json::Value {
+        let value = self.generate();
+
+        if self.numeric {
+            if let Ok(number) = serde_json::from_str::<serde_json::Number>(&value) {
+                return serde_json::Value::Number(number);
+            }
+        }
+
+        serde_json::Value::String(value)
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = self.rng.lock().unwrap();
+
+        match &self.generator {
+            Generator::Uniform {
+                minimum,
+                maximum,
+                inclusive: true,
+            } => format!(
+                "{}",
+                distributions::Uniform::new_inclusive(*minimum, *maximum).sample(&mut *rng)
+            ),
+            Generator::Uniform {
+                minimum, maximum, ..
+            } => format!("{}", rng.gen_range(*minimum, *maximum)),
+            Generator::Zipf(zipf) => format!("{}", zipf.sample(&mut *rng)),
+            Generator::Normal(normal) => format!("{}", normal.sample(&mut *rng)),
+            Generator::Set(values) | Generator::Pool(values) => {
+                values[rng.gen_range(0, values.len())].clone()
+            }
+            Generator::String { length } => (&mut *rng)
+                .sample_iter(Alphanumeric)
+                .take(*length)
+                .collect(),
+            Generator::Uuid => uuid::Builder::from_bytes(rng.gen())
+                .set_variant(uuid::Variant::RFC4122)
+                .set_version(uuid::Version::Random)
+                .build()
+                .to_hyphenated()
+                .to_string(),
+            Generator::Date(range) => range.sample(&mut *rng),
+            Generator::Sequence { step } => {
+                format!("{}", self.counter.fetch_add(*step, Ordering::SeqCst))
+            }
+            Generator::Sql(_) => unreachable!("SQL variables are loaded before the run"),
+        }
+    }
+}
+
+fn check_range(minimum: i64, maximum: i64) -> crate::Result<()> {
+    if minimum > maximum {
+        Err(Error::InvalidVariable(format!(
+            "minimum {} is larger than maximum {}",
+            minimum, maximum
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads all values of one column from a CSV (by header) or a JSON lines
+/// (by key) file.
+fn read_column(path: &PathBuf, column: &str) -> crate::Result<Vec<String>> {
+    let values = match path.extension().and_then(|s| s.to_str()) {
+        Some("csv") => {
+            let mut reader = csv::Reader::from_path(path)?;
+
+            let index = reader
+                .headers()?
+                .iter()
+                .position(|header| header == column)
+                .ok_or_else(|| {
+                    Error::InvalidVariable(format!(
+                        "column '{}' not found in {}",
+                        column,
+                        path.display()
+                    ))
+                })?;
+
+            let mut values = Vec::new();
+
+            for record in reader.records() {
+                if let Some(value) = record?.get(index) {
+                    values.push(value.to_string());
+                }
+            }
+
+            values
+        }
+        Some("jsonl") | Some("ndjson") => {
+            let mut values = Vec::new();
+
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let json: serde_json::Value = serde_json::from_str(&line)?;
+
+                match &json[column] {
+                    serde_json::Value::Null => (),
+                    serde_json::Value::String(s) => values.push(s.clone()),
+                    value => values.push(value.to_string()),
+                }
+            }
+
+            values
+        }
+        _ => {
+            return Err(Error::InvalidVariable(format!(
+                "data file {} should be either .csv or .jsonl",
+                path.display()
+            )))
+        }
+    };
+
+    if values.is_empty() {
+        Err(Error::InvalidVariable(format!(
+            "no values for column '{}' in {}",
+            column,
+            path.display()
+        )))
+    } else {
+        Ok(values)
+    }
+}
+
+/// Zipf distribution over `[minimum, maximum]`, `minimum` being the hottest
+/// key. Sampled with rejection-inversion, so it needs no lookup tables even
+/// for millions of keys.
+#[derive(Debug, Clone)]
+struct Zipf {
+    minimum: i64,
+    s: f64,
+    t: f64,
+    q: f64,
+}
+
+impl Zipf {
+    fn new(minimum: i64, maximum: i64, s: f64) -> crate::Result<Self> {
+        let n = maximum
+            .checked_sub(minimum)
+            .and_then(|span| span.checked_add(1))
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "zipf range from {} to {} is too large",
+                    minimum, maximum
+                ))
+            })? as f64;
+        let q = if s != 1.0 { 1.0 / (1.0 - s) } else { 0.0 };
+
+        let t = if s != 1.0 {
+            (n.powf(1.0 - s) - s) * q
+        } else {
+            1.0 + n.ln()
+        };
+
+        Ok(Self { minimum, s, t, q })
+    }
+
+    fn inv_cdf(&self, p: f64) -> f64 {
+        let pt = p * self.t;
+
+        if pt <= 1.0 {
+            pt
+        } else if self.s != 1.0 {
+            (pt * (1.0 - self.s) + self.s).powf(self.q)
+        } else {
+            (pt - 1.0).exp()
+        }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> i64 {
+        loop {
+            let inv_b = self.inv_cdf(rng.gen::<f64>());
+            let x = (inv_b + 1.0).floor();
+            let mut ratio = x.powf(-self.s);
+
+            if x > 1.0 {
+                ratio *= inv_b.powf(self.s);
+            }
+
+            if rng.gen::<f64>() < ratio {
+                return self.minimum + x as i64 - 1;
+            }
+        }
+    }
+}
+
+/// Normal distribution, rounded to an integer and optionally clamped.
+#[derive(Debug, Clone)]
+struct Normal {
+    mean: f64,
+    std_dev: f64,
+    minimum: Option<i64>,
+    maximum: Option<i64>,
+}
+
+impl Normal {
+    fn sample<R: Rng>(&self, rng: &mut R) -> i64 {
+        // Box-Muller; `1.0 - gen()` keeps the logarithm away from zero.
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+
+        let mut value = (self.mean + z * self.std_dev).round() as i64;
+
+        if let Some(minimum) = self.minimum {
+            value = value.max(minimum);
+        }
+
+        if let Some(maximum) = self.maximum {
+            value = value.min(maximum);
+        }
+
+        value
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DateRange {
+    start: DateTime<Utc>,
+    seconds: i64,
+    format: Option<String>,
+}
+
+impl DateRange {
+    fn new(start: &str, end: &str, format: Option<String>) -> crate::Result<Self> {
+        let start = Self::parse(start)?;
+        let end = Self::parse(end)?;
+        let seconds = (end - start).num_seconds();
+
+        if seconds < 0 {
+            return Err(Error::InvalidVariable(format!(
+                "date range starts after it ends ({} > {})",
+                start, end
+            )));
+        }
+
+        // Sampling picks one of `seconds + 1` offsets.
+        if seconds.checked_add(1).is_none() {
+            return Err(Error::InvalidConfig(format!(
+                "date range from {} to {} is too large",
+                start, end
+            )));
+        }
+
+        // Formatting with an invalid specifier would only panic during the
+        // run.
+        if let Some(ref format) = format {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(Error::InvalidConfig(format!(
+                    "date format '{}' is not a valid strftime format",
+                    format
+                )));
+            }
+        }
+
+        Ok(Self {
+            start,
+            seconds,
+            format,
+        })
+    }
+
+    fn parse(s: &str) -> crate::Result<DateTime<Utc>> {
+        if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+            return Ok(date.with_timezone(&Utc));
+        }
+
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+            .map_err(|_| {
+                Error::InvalidVariable(format!(
+                    "date '{}' should be in RFC 3339 or YYYY-MM-DD format",
+                    s
+                ))
+            })
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> String {
+        let date = self.start + Duration::seconds(rng.gen_range(0, self.seconds + 1));
+
+        match &self.format {
+            Some(format) => date.format(format).to_string(),
+            None => date.to_rfc3339(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(definition: &str) -> QueryVariable {
+        toml::from_str(definition).unwrap()
+    }
+
+    fn numbers(variable: &QueryVariable, n: usize) -> Vec<i64> {
+        (0..n)
+            .map(|_| variable.generate().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn legacy_range_excludes_the_maximum() {
+        let variable = variable("minimum = 1\nmaximum = 3\nseed = 7");
+        let values = numbers(&variable, 1000);
+
+        assert!(values.iter().all(|v| (1..3).contains(v)));
+        assert!(values.contains(&1) && values.contains(&2));
+    }
+
+    #[test]
+    fn legacy_range_needs_room_below_the_maximum() {
+        let result = toml::from_str::<QueryVariable>("minimum = 3\nmaximum = 3");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn uniform_includes_the_maximum() {
+        let variable = variable("kind = 'uniform'\nminimum = 1\nmaximum = 3\nseed = 7");
+        let values = numbers(&variable, 1000);
+
+        assert!(values.iter().all(|v| (1..=3).contains(v)));
+        assert!(values.contains(&3));
+    }
+
+    #[test]
+    fn zipf_stays_in_range_and_favours_the_minimum() {
+        let variable = variable("kind = 'zipf'\nminimum = 10\nmaximum = 20\nseed = 7");
+        let values = numbers(&variable, 1000);
+
+        assert!(values.iter().all(|v| (10..=20).contains(v)));
+
+        let hottest = values.iter().filter(|v| **v == 10).count();
+        let coldest = values.iter().filter(|v| **v == 20).count();
+        assert!(hottest > coldest);
+    }
+
+    #[test]
+    fn zipf_rejects_a_range_wider_than_i64() {
+        let definition = format!(
+            "kind = 'zipf'\nminimum = {}\nmaximum = {}",
+            std::i64::MIN,
+            std::i64::MAX
+        );
+
+        assert!(toml::from_str::<QueryVariable>(&definition).is_err());
+    }
+
+    #[test]
+    fn normal_is_clamped() {
+        let variable = variable(
+            "kind = 'normal'\nmean = 50.0\nstd_dev = 100.0\nminimum = 0\nmaximum = 100\nseed = 7",
+        );
+        let values = numbers(&variable, 1000);
+
+        assert!(values.iter().all(|v| (0..=100).contains(v)));
+        assert!(values.contains(&0) && values.contains(&100));
+    }
+
+    #[test]
+    fn set_picks_one_of_its_values() {
+        let variable = variable("kind = 'set'\nvalues = ['a', 'b']\nseed = 7");
+
+        for _ in 0..100 {
+            let value = variable.generate();
+            assert!(value == "a" || value == "b");
+        }
+    }
+
+    #[test]
+    fn string_has_the_given_length() {
+        let variable = variable("kind = 'string'\nlength = 12\nseed = 7");
+        let value = variable.generate();
+
+        assert_eq!(12, value.len());
+        assert!(value.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn uuid_is_a_random_uuid() {
+        let variable = variable("kind = 'uuid'\nseed = 7");
+        let value = uuid::Uuid::parse_str(&variable.generate()).unwrap();
+
+        assert_eq!(Some(uuid::Version::Random), value.get_version());
+    }
+
+    #[test]
+    fn date_stays_in_range() {
+        let variable = variable(
+            "kind = 'date'\nstart = '2020-01-01'\nend = '2020-01-02'\nformat = '%Y-%m-%d'\nseed = 7",
+        );
+
+        for _ in 0..100 {
+            let value = variable.generate();
+            assert!(value == "2020-01-01" || value == "2020-01-02");
+        }
+    }
+
+    #[test]
+    fn date_rejects_an_invalid_format() {
+        let definition = "kind = 'date'\nstart = '2020-01-01'\nend = '2020-01-02'\nformat = '%Q'";
+        assert!(toml::from_str::<QueryVariable>(definition).is_err());
+    }
+
+    #[test]
+    fn sequence_counts_by_its_step() {
+        let variable = variable("kind = 'sequence'\nstart = 5\nstep = 3");
+        assert_eq!(vec![5, 8, 11], numbers(&variable, 3));
+    }
+
+    #[test]
+    fn file_picks_from_the_column() {
+        let path = std::env::temp_dir().join(format!("variable-{}.csv", std::process::id()));
+        std::fs::write(&path, "id,name\n1,a\n2,b\n").unwrap();
+
+        let definition = format!(
+            "kind = 'file'\npath = '{}'\ncolumn = 'name'\nseed = 7",
+            path.display()
+        );
+        let variable = variable(&definition);
+        std::fs::remove_file(&path).unwrap();
+
+        for _ in 0..100 {
+            let value = variable.generate();
+            assert!(value == "a" || value == "b");
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_values() {
+        for definition in &[
+            "kind = 'uniform'\nminimum = 0\nmaximum = 1000000\nseed = 42",
+            "kind = 'zipf'\nminimum = 0\nmaximum = 1000000\nseed = 42",
+            "kind = 'string'\nlength = 16\nseed = 42",
+            "kind = 'uuid'\nseed = 42",
+        ] {
+            let first = variable(definition);
+            let second = variable(definition);
+
+            let first: Vec<String> = (0..20).map(|_| first.generate()).collect();
+            let second: Vec<String> = (0..20).map(|_| second.generate()).collect();
+
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn the_test_seed_gives_every_variable_its_own_stream() {
+        let definition = "kind = 'uniform'\nminimum = 0\nmaximum = 1000000";
+
+        let mut first = variable(definition);
+        let mut again = variable(definition);
+        let mut other = variable(definition);
+
+        first.seed_from(42, "id");
+        again.seed_from(42, "id");
+        other.seed_from(42, "other");
+
+        let first = numbers(&first, 20);
+
+        assert_eq!(first, numbers(&again, 20));
+        assert_ne!(first, numbers(&other, 20));
+    }
+}
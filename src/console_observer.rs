@@ -1,3 +1,4 @@
+use crate::histogram;
 use console::style;
 use hdrhistogram::Histogram;
 use metrics_core::{Drain, Key, Observer};
@@ -11,7 +12,7 @@ pub struct ConsoleObserver {
 impl Default for ConsoleObserver {
     fn default() -> Self {
         Self {
-            response_times: Histogram::new(3).unwrap(),
+            response_times: histogram::new(),
             successful: 0,
             error: 0,
         }
@@ -38,7 +39,7 @@ impl Observer for ConsoleObserver {
     fn observe_histogram(&mut self, key: Key, values: &[u64]) {
         if key.name().as_ref() == "response_time" {
             for value in values {
-                self.response_times.record(*value).unwrap();
+                histogram::record(&mut self.response_times, *value);
             }
         }
     }
@@ -0,0 +1,99 @@
+use crate::{metrics_sender::MetricsSender, metrics_sink::elastic_auth, ElasticInitOpt};
+use serde_json::{json, Value};
+
+/// The fields of a `ResponseTime` document and their types.
+const RESPONSE_TIME_FIELDS: &[(&str, &str)] = &[
+    ("commit", "keyword"),
+    ("connector", "keyword"),
+    ("version", "keyword"),
+    ("queryName", "keyword"),
+    ("endpoint", "keyword"),
+    ("batch", "long"),
+    ("rps", "long"),
+    ("successes", "long"),
+    ("failures", "long"),
+    ("p50", "long"),
+    ("p90", "long"),
+    ("p95", "long"),
+    ("p99", "long"),
+    ("p999", "long"),
+    ("min", "long"),
+    ("max", "long"),
+    ("mean", "double"),
+    ("stddev", "double"),
+    ("histogram", "binary"),
+    ("time", "date"),
+];
+
+/// The fields of a per-second `SeriesPoint` document and their types.
+const SERIES_FIELDS: &[(&str, &str)] = &[
+    ("commit", "keyword"),
+    ("connector", "keyword"),
+    ("version", "keyword"),
+    ("queryName", "keyword"),
+    ("endpoint", "keyword"),
+    ("batch", "long"),
+    ("rps", "long"),
+    ("second", "long"),
+    ("sent", "long"),
+    ("completed", "long"),
+    ("errors", "long"),
+    ("p50", "long"),
+    ("p99", "long"),
+    ("time", "date"),
+];
+
+/// The expected fields of the results index, or of its `-series`
+/// companion.
+pub fn fields(index: &str) -> &'static [(&'static str, &'static str)] {
+    if index.ends_with("-series") {
+        SERIES_FIELDS
+    } else {
+        RESPONSE_TIME_FIELDS
+    }
+}
+
+/// The mappings to create the index with.
+pub fn mappings(index: &str) -> Value {
+    let properties: serde_json::Map<String, Value> = fields(index)
+        .iter()
+        .map(|(name, typ)| (name.to_string(), json!({ "type": typ })))
+        .collect();
+
+    json!({
+        "mappings": {
+            "properties": properties,
+        }
+    })
+}
+
+/// The differences between the expected fields and the `properties` of an
+/// existing index.
+pub fn incompatibilities(index: &str, properties: &Value) -> Vec<String> {
+    fields(index)
+        .iter()
+        .filter_map(|(name, typ)| match properties[name]["type"].as_str() {
+            Some(actual) if actual == *typ => None,
+            Some(actual) => Some(format!("`{}` is {}, expected {}", name, actual, typ)),
+            None => Some(format!("`{}` is not mapped, expected {}", name, typ)),
+        })
+        .collect()
+}
+
+/// Creates the results index and its `-series` companion with explicit
+/// mappings, or checks the mappings of the ones already there.
+pub async fn init(opts: ElasticInitOpt) -> crate::Result<()> {
+    let auth = elastic_auth(&opts.elastic, None, None, None)?;
+    let sender = MetricsSender::new(&opts.elastic_endpoint, &opts.index, auth);
+
+    for index in &[opts.index.clone(), format!("{}-series", opts.index)] {
+        if sender.create_index(index, &mappings(index)).await? {
+            println!("Created index {}", index);
+        } else {
+            sender.verify_index(index).await?;
+            println!("Index {} exists with compatible mappings", index);
+        }
+    }
+
+    Ok(())
+}
@@ -16,15 +16,51 @@ pub enum Error {
         commit_id: String,
         connector: String,
     },
+    #[error("No measurement of connector '{}' matches '{}'", connector, reference)]
+    UnknownMeasurement {
+        reference: String,
+        connector: String,
+    },
+    #[error("'{}' is ambiguous, it matches the commits {}", reference, candidates)]
+    AmbiguousMeasurement {
+        reference: String,
+        candidates: String,
+    },
+    #[error(
+        "Found no {} measurement of connector '{}' to compare, choose one with --{}",
+        side,
+        connector,
+        side
+    )]
+    MissingComparison {
+        side: &'static str,
+        connector: String,
+    },
     #[error("Endpoint type '{}' is not supported", _0)]
     InvalidEndpointType(String),
     #[error("Database type '{}' is not supported", _0)]
     InvalidDatabaseType(String),
+    #[error("Invalid configuration: {}", _0)]
+    InvalidConfig(String),
+    #[error("Environment variable '{}' is not set", _0)]
+    MissingEnvVar(String),
+    #[error("Invalid variable definition: {}", _0)]
+    InvalidVariable(String),
     #[error("Query {} returned an error: {}", query, error)]
     InvalidQuery {
         query: String,
         error: serde_json::Value,
     },
+    #[error("The {} hook `{}` failed: {}", stage, hook, reason)]
+    Hook {
+        stage: &'static str,
+        hook: String,
+        reason: String,
+    },
+    #[error("Elastic Search index '{}' is not compatible: {}", index, reason)]
+    IncompatibleIndex { index: String, reason: String },
+    #[error("The database returned no id for the new {}", _0)]
+    MissingId(&'static str),
     #[error("Error querying database: {}", _0)]
     Quaint(quaint::error::Error),
     #[error("IO Error: {}", _0)]
@@ -35,6 +71,8 @@ pub enum Error {
     Http(Box<dyn std::error::Error>),
     #[error("Error in SSH connection: {}", _0)]
     Ssh(ssh2::Error),
+    #[error("Error in reading or writing a histogram: {}", _0)]
+    Histogram(String),
     #[error("Error in generating metrics: {}", _0)]
     MetricsError(metrics_runtime::BuilderError),
 }
@@ -87,12 +125,24 @@ impl From<ssh2::Error> for Error {
     }
 }
 
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Self::Serialization(Box::new(e))
+    }
+}
+
 impl From<toml::de::Error> for Error {
     fn from(e: toml::de::Error) -> Self {
         Self::Serialization(Box::new(e))
     }
 }
 
+impl From<toml::ser::Error> for Error {
+    fn from(e: toml::ser::Error) -> Self {
+        Self::Serialization(Box::new(e))
+    }
+}
+
 impl From<uuid::Error> for Error {
     fn from(e: uuid::Error) -> Self {
         Self::Serialization(Box::new(e))
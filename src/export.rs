@@ -0,0 +1,240 @@
+use crate::{
+    error::Error,
+    json_observer::ResponseTime,
+    metrics_sink::{MetricsSink, RunInfo},
+    ExportOpt,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use quaint::{prelude::*, single::Quaint};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The columns of `response_time` exported as they are.
+const RESPONSE_TIME_COLUMNS: &[&str] = &[
+    "run",
+    "query_name",
+    "endpoint",
+    "batch",
+    "rps",
+    "time",
+    "successes",
+    "failures",
+    "p50",
+    "p90",
+    "p95",
+    "p99",
+    "p999",
+    "min",
+    "max",
+    "mean",
+    "stddev",
+    "histogram",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Csv,
+    /// One JSON object per line, so results can be appended.
+    JsonLines,
+}
+
+impl FileFormat {
+    /// The format matching the file extension.
+    pub fn from_path(path: &Path) -> crate::Result<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("csv") => Ok(Self::Csv),
+            Some("json") | Some("jsonl") | Some("ndjson") => Ok(Self::JsonLines),
+            _ => Err(Error::InvalidConfig(format!(
+                "cannot write results to {}, the file should end with .csv, .json or .jsonl",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// One result in a file: the stored metrics with the run, commit, version
+/// and connector they were measured on. Columns added later are optional,
+/// so older measurements can be exported too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResultRow {
+    identifier: Option<String>,
+    run: Option<i64>,
+    host: Option<String>,
+    commit: String,
+    version: String,
+    connector: String,
+    query_name: String,
+    endpoint: Option<String>,
+    batch: Option<i64>,
+    rps: i64,
+    time: DateTime<Utc>,
+    successes: i64,
+    failures: i64,
+    p50: i64,
+    p90: Option<i64>,
+    p95: i64,
+    p99: i64,
+    p999: Option<i64>,
+    min: Option<i64>,
+    max: Option<i64>,
+    mean: Option<f64>,
+    stddev: Option<f64>,
+    histogram: Option<String>,
+}
+
+impl ResultRow {
+    fn new(identifier: &str, run: Option<&RunInfo>, metrics: &ResponseTime) -> Self {
+        Self {
+            identifier: Some(
+                run.map(|run| run.identifier.as_str())
+                    .unwrap_or(identifier)
+                    .into(),
+            ),
+            run: run.and_then(|run| run.id),
+            host: run.map(|run| run.host.clone()),
+            commit: metrics.commit().into(),
+            version: metrics.version().into(),
+            connector: metrics.connector().into(),
+            query_name: metrics.query_name().into(),
+            endpoint: metrics.endpoint().map(String::from),
+            batch: metrics.batch().map(|b| b as i64),
+            rps: metrics.rps() as i64,
+            time: metrics.time(),
+            successes: metrics.successes() as i64,
+            failures: metrics.failures() as i64,
+            p50: metrics.p50() as i64,
+            p90: Some(metrics.p90() as i64),
+            p95: metrics.p95() as i64,
+            p99: metrics.p99() as i64,
+            p999: Some(metrics.p999() as i64),
+            min: Some(metrics.min() as i64),
+            max: Some(metrics.max() as i64),
+            mean: Some(metrics.mean()),
+            stddev: Some(metrics.stddev()),
+            histogram: metrics.histogram().map(String::from),
+        }
+    }
+}
+
+/// Adds the rows to the end of the file, creating it if needed. A CSV file
+/// gets its header when empty, a JSON Lines file one line per row.
+pub fn append(path: &Path, format: FileFormat, rows: &[ResultRow]) -> crate::Result<()> {
+    match format {
+        FileFormat::Csv => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let empty = file.metadata()?.len() == 0;
+
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(empty)
+                .from_writer(file);
+
+            for row in rows {
+                writer.serialize(row)?;
+            }
+
+            writer.flush()?;
+        }
+        FileFormat::JsonLines => {
+            let mut lines = String::new();
+
+            for row in rows {
+                lines.push_str(&serde_json::to_string(row)?);
+                lines.push('\n');
+            }
+
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            file.write_all(lines.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes every result to a CSV or JSON Lines file as soon as its run finishes.
+pub struct FileSink {
+    path: PathBuf,
+    format: FileFormat,
+    identifier: String,
+    /// The current run, if one was started.
+    run: Mutex<Option<RunInfo>>,
+}
+
+impl FileSink {
+    pub fn new(path: &Path, identifier: &str) -> crate::Result<Self> {
+        Ok(Self {
+            format: FileFormat::from_path(path)?,
+            path: path.into(),
+            identifier: identifier.into(),
+            run: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl MetricsSink for FileSink {
+    async fn start_run(&self, run: &RunInfo) -> crate::Result<Option<i64>> {
+        *self.run.lock().unwrap() = Some(run.clone());
+        Ok(None)
+    }
+
+    async fn store(&self, metrics: &ResponseTime) -> crate::Result<()> {
+        let row = ResultRow::new(&self.identifier, self.run.lock().unwrap().as_ref(), metrics);
+        append(&self.path, self.format, &[row])
+    }
+}
+
+/// Writes the selected measurements of the secondary storage to a file.
+pub async fn export(opts: ExportOpt) -> crate::Result<()> {
+    let format = FileFormat::from_path(&opts.output)?;
+    let db = Quaint::new(&opts.secondary_storage).await?;
+
+    let mut select = Select::from_table("response_time")
+        .column(Column::from(("version", "commit_id")).alias("commit"))
+        .column(Column::from(("version", "version")).alias("version"))
+        .column(Column::from(("version", "connector")).alias("connector"))
+        .column(Column::from(("run", "identifier")).alias("identifier"))
+        .column(Column::from(("run", "host")).alias("host"))
+        .inner_join(
+            "version".on(("version", "id").equals(Column::from(("response_time", "version")))),
+        )
+        .left_join("run".on(("run", "id").equals(Column::from(("response_time", "run")))))
+        .order_by(("response_time", "id").ascend());
+
+    for column in RESPONSE_TIME_COLUMNS {
+        select = select.column(Column::from(("response_time", *column)).alias(*column));
+    }
+
+    if let Some(ref commit) = opts.commit {
+        select = select.and_where(("version", "commit_id").begins_with(commit.as_str()));
+    }
+
+    if let Some(connector) = opts.connector {
+        select = select.and_where(("version", "connector").equals(connector.to_string()));
+    }
+
+    if let Some(ref query) = opts.query {
+        select = select.and_where(("response_time", "query_name").equals(query.as_str()));
+    }
+
+    let rows: Vec<ResultRow> = quaint::serde::from_rows(db.select(select).await?)?;
+
+    if opts.output.exists() {
+        fs::remove_file(&opts.output)?;
+    }
+
+    append(&opts.output, format, &rows)?;
+
+    println!(
+        "Exported {} results to {}",
+        rows.len(),
+        opts.output.display()
+    );
+
+    Ok(())
+}
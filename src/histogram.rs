@@ -0,0 +1,50 @@
+use crate::error::Error;
+use hdrhistogram::{
+    serialization::{Deserializer, Serializer, V2DeflateSerializer},
+    Histogram,
+};
+use std::io::Cursor;
+
+/// The longest response time we can record, in nanoseconds. Requests time
+/// out after ten seconds, so anything longer is clamped.
+const HIGHEST_TRACKABLE: u64 = 60_000_000_000;
+
+/// A histogram for response times in nanoseconds, with three significant
+/// digits.
+pub fn new() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, HIGHEST_TRACKABLE, 3).unwrap()
+}
+
+/// A histogram for response times in microseconds, with two significant
+/// digits. Small enough to keep one for every second of a run.
+pub fn coarse() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, HIGHEST_TRACKABLE / 1_000, 2).unwrap()
+}
+
+/// Records a value, clamping it to the bounds of the histogram instead of
+/// failing.
+pub fn record(histogram: &mut Histogram<u64>, value: u64) {
+    let value = value.max(histogram.low()).min(histogram.high());
+    histogram.record(value).ok();
+}
+
+/// Serializes the histogram in the V2 format with deflate compression, as
+/// base64 text.
+pub fn encode(histogram: &Histogram<u64>) -> crate::Result<String> {
+    let mut bytes = Vec::new();
+
+    V2DeflateSerializer::new()
+        .serialize(histogram, &mut bytes)
+        .map_err(|e| Error::Histogram(format!("{:?}", e)))?;
+
+    Ok(base64::encode(&bytes))
+}
+
+/// Reads a histogram written by `encode`.
+pub fn decode(encoded: &str) -> crate::Result<Histogram<u64>> {
+    let bytes = base64::decode(encoded).map_err(|e| Error::Histogram(format!("{}", e)))?;
+
+    Deserializer::new()
+        .deserialize(&mut Cursor::new(bytes))
+        .map_err(|e| Error::Histogram(format!("{:?}", e)))
+}
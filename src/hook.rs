@@ -0,0 +1,80 @@
+use crate::{config::SingleQuery, error::Error, requester::Requester};
+use quaint::{prelude::*, single::Quaint};
+use serde::Deserialize;
+use std::fmt;
+use tokio::process::Command;
+
+/// An action run before or after every (query, rps) run of a test run,
+/// e.g. to reset the database after mutations. Hooks are not part of the
+/// measurement.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Hook {
+    /// Raw SQL, executed through quaint.
+    Sql { sql: String, url: String },
+    /// A GraphQL operation sent to the benchmarked endpoint.
+    GraphQL { graphql: String },
+    /// A shell command, run with `sh -c`.
+    Shell { shell: String },
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sql { sql, url: _ } => write!(f, "sql: {}", sql),
+            Self::GraphQL { graphql } => write!(f, "graphql: {}", graphql.trim()),
+            Self::Shell { shell } => write!(f, "shell: {}", shell),
+        }
+    }
+}
+
+impl Hook {
+    pub async fn run(
+        &self,
+        stage: &'static str,
+        requester: &Requester,
+        query: &SingleQuery,
+    ) -> crate::Result<()> {
+        let failed = |reason: String| Error::Hook {
+            stage,
+            hook: format!("{}", self),
+            reason,
+        };
+
+        match self {
+            Self::Sql { sql, url } => {
+                let db = Quaint::new(url)
+                    .await
+                    .map_err(|e| failed(format!("{}", e)))?;
+
+                db.raw_cmd(sql)
+                    .await
+                    .map_err(|e| failed(format!("{}", e)))?;
+            }
+            Self::GraphQL { graphql } => {
+                requester
+                    .execute(graphql, query)
+                    .await
+                    .map_err(|e| failed(format!("{}", e)))?;
+            }
+            Self::Shell { shell } => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(shell)
+                    .output()
+                    .await
+                    .map_err(|e| failed(format!("{}", e)))?;
+
+                if !output.status.success() {
+                    return Err(failed(format!(
+                        "{}: {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
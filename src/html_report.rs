@@ -0,0 +1,509 @@
+//! A single static HTML file comparing two measurements, with the charts
+//! drawn as inline SVG so the file needs nothing else to be viewed.
+
+use crate::{
+    reporter::escape,
+    response_summary::{QueryComparison, ResponseSamples, ResponseSummary, TrendSummary},
+    significance::{Change, Verdict},
+    HtmlReportOpt,
+};
+use chrono::Utc;
+use std::{collections::BTreeMap, fmt::Write, fs};
+
+const WIDTH: f64 = 720.0;
+const MARGIN: f64 = 48.0;
+const LEGEND: f64 = 140.0;
+const LINE_HEIGHT: f64 = 240.0;
+const BAR_LABEL: f64 = 300.0;
+const BAR_HEIGHT: f64 = 18.0;
+
+const BASE_COLOR: &str = "#9e9e9e";
+const HEAD_COLOR: &str = "#1e88e5";
+const IMPROVED_COLOR: &str = "#43a047";
+const REGRESSED_COLOR: &str = "#e53935";
+
+const STYLE: &str = "
+body { font-family: -apple-system, BlinkMacSystemFont, sans-serif; margin: 2em auto; max-width: 760px; color: #212121; }
+h2 { margin-top: 2em; border-bottom: 1px solid #e0e0e0; }
+svg { display: block; margin: 1em 0; }
+svg text { font-size: 11px; fill: #424242; }
+svg .title { font-size: 13px; font-weight: bold; }
+code { background: #f5f5f5; padding: 0 0.3em; }
+";
+
+/// A line in a line chart.
+struct Series {
+    name: &'static str,
+    color: &'static str,
+    dashed: bool,
+    points: Vec<(f64, f64)>,
+}
+
+/// A bar in a horizontal bar chart.
+struct Bar {
+    label: String,
+    value: f64,
+    color: &'static str,
+}
+
+fn millis(nanos: f64) -> f64 {
+    nanos / 1_000_000.0
+}
+
+fn verdict_color(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Improved => IMPROVED_COLOR,
+        Verdict::Regressed => REGRESSED_COLOR,
+        Verdict::Inconclusive => BASE_COLOR,
+    }
+}
+
+/// A line chart of the series, starting the y axis at zero. `x_ticks` are
+/// the labeled positions on the x axis.
+fn line_chart(title: &str, unit: &str, x_ticks: &[(f64, String)], series: &[Series]) -> String {
+    let xs = || series.iter().flat_map(|s| s.points.iter().map(|p| p.0));
+
+    let x_min = xs().fold(f64::INFINITY, f64::min);
+    let x_max = xs().fold(f64::NEG_INFINITY, f64::max);
+
+    let y_max = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.1))
+        .fold(0.0, f64::max);
+
+    let y_max = if y_max > 0.0 { y_max * 1.1 } else { 1.0 };
+
+    let plot_width = WIDTH - 2.0 * MARGIN - LEGEND;
+    let plot_height = LINE_HEIGHT - 2.0 * MARGIN;
+
+    let x = |v: f64| {
+        if x_max > x_min {
+            MARGIN + (v - x_min) / (x_max - x_min) * plot_width
+        } else {
+            MARGIN + plot_width / 2.0
+        }
+    };
+
+    let y = |v: f64| LINE_HEIGHT - MARGIN - v / y_max * plot_height;
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = WIDTH,
+        h = LINE_HEIGHT
+    )
+    .unwrap();
+
+    writeln!(
+        svg,
+        r#"<text class="title" x="{}" y="20">{}</text>"#,
+        MARGIN,
+        escape(title)
+    )
+    .unwrap();
+
+    writeln!(
+        svg,
+        r##"<path d="M{l} {t} V{b} H{r}" fill="none" stroke="#757575"/>"##,
+        l = MARGIN,
+        t = MARGIN,
+        b = LINE_HEIGHT - MARGIN,
+        r = MARGIN + plot_width
+    )
+    .unwrap();
+
+    for i in 0..=4 {
+        let value = y_max * f64::from(i) / 4.0;
+
+        writeln!(
+            svg,
+            r##"<line x1="{l}" x2="{r}" y1="{y}" y2="{y}" stroke="#eeeeee"/><text x="{tx}" y="{ty}" text-anchor="end">{v:.2}</text>"##,
+            l = MARGIN,
+            r = MARGIN + plot_width,
+            y = y(value),
+            tx = MARGIN - 4.0,
+            ty = y(value) + 4.0,
+            v = value
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        svg,
+        r#"<text x="4" y="{}">{}</text>"#,
+        MARGIN - 8.0,
+        escape(unit)
+    )
+    .unwrap();
+
+    for (position, label) in x_ticks {
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            x(*position),
+            LINE_HEIGHT - MARGIN + 16.0,
+            escape(label)
+        )
+        .unwrap();
+    }
+
+    for (i, s) in series.iter().enumerate() {
+        let points: Vec<String> = s
+            .points
+            .iter()
+            .map(|(px, py)| format!("{:.1},{:.1}", x(*px), y(*py)))
+            .collect();
+
+        writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2"{}/>"#,
+            points.join(" "),
+            s.color,
+            if s.dashed {
+                r#" stroke-dasharray="5,3""#
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+
+        for (px, py) in s.points.iter() {
+            writeln!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#,
+                x(*px),
+                y(*py),
+                s.color
+            )
+            .unwrap();
+        }
+
+        let legend_y = MARGIN + 16.0 * i as f64;
+        let legend_x = WIDTH - LEGEND;
+
+        writeln!(
+            svg,
+            r#"<line x1="{}" x2="{}" y1="{y}" y2="{y}" stroke="{}" stroke-width="2"{}/><text x="{}" y="{}">{}</text>"#,
+            legend_x,
+            legend_x + 20.0,
+            s.color,
+            if s.dashed {
+                r#" stroke-dasharray="5,3""#
+            } else {
+                ""
+            },
+            legend_x + 26.0,
+            legend_y + 4.0,
+            s.name,
+            y = legend_y
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A horizontal bar chart, with bars growing left of zero for negative
+/// values.
+fn bar_chart(title: &str, unit: &str, bars: &[Bar]) -> String {
+    let min = bars.iter().map(|b| b.value).fold(0.0, f64::min);
+    let max = bars.iter().map(|b| b.value).fold(0.0, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+
+    let plot_width = WIDTH - BAR_LABEL - MARGIN - 16.0;
+    let height = 32.0 + bars.len() as f64 * (BAR_HEIGHT + 4.0) + 8.0;
+
+    let x = |v: f64| BAR_LABEL + (v - min) / range * plot_width;
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = WIDTH,
+        h = height
+    )
+    .unwrap();
+
+    writeln!(
+        svg,
+        r#"<text class="title" x="0" y="20">{}</text>"#,
+        escape(title)
+    )
+    .unwrap();
+
+    for (i, bar) in bars.iter().enumerate() {
+        let top = 32.0 + i as f64 * (BAR_HEIGHT + 4.0);
+        let left = x(bar.value.min(0.0));
+        let width = (x(bar.value.max(0.0)) - left).max(1.0);
+
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">{}</text><rect x="{:.1}" y="{}" width="{:.1}" height="{}" fill="{}"/><text x="{:.1}" y="{}">{:+.2}{}</text>"#,
+            BAR_LABEL - 8.0,
+            top + BAR_HEIGHT - 5.0,
+            escape(&bar.label),
+            left,
+            top,
+            width,
+            BAR_HEIGHT,
+            bar.color,
+            left + width + 4.0,
+            top + BAR_HEIGHT - 5.0,
+            bar.value,
+            escape(unit)
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        svg,
+        r##"<line x1="{x}" x2="{x}" y1="28" y2="{b}" stroke="#757575"/>"##,
+        x = x(0.0),
+        b = height - 4.0
+    )
+    .unwrap();
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The p50 and p99 of the samples of one query and batch size, by rate.
+fn latency_series(
+    samples: &[&ResponseSamples],
+    side: (&'static str, &'static str),
+    color: &'static str,
+) -> Vec<Series> {
+    let (p50, p99) = side;
+
+    vec![
+        Series {
+            name: p50,
+            color,
+            dashed: false,
+            points: samples
+                .iter()
+                .map(|s| (s.rps() as f64, millis(s.mean().0)))
+                .collect(),
+        },
+        Series {
+            name: p99,
+            color,
+            dashed: true,
+            points: samples
+                .iter()
+                .map(|s| (s.rps() as f64, millis(s.mean().2)))
+                .collect(),
+        },
+    ]
+}
+
+/// Samples grouped by query, endpoint and batch size, ordered by rate.
+fn by_query<'a>(
+    samples: impl Iterator<Item = &'a ResponseSamples>,
+) -> BTreeMap<(String, Option<String>, Option<u64>), Vec<&'a ResponseSamples>> {
+    let mut queries: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+    for s in samples {
+        let endpoint = s.endpoint().map(String::from);
+
+        queries
+            .entry((s.query_name().to_string(), endpoint, s.batch()))
+            .or_default()
+            .push(s);
+    }
+
+    queries
+}
+
+fn latency_section(summary: &ResponseSummary) -> String {
+    let previous = by_query(summary.previous_samples());
+    let next = by_query(summary.next_samples());
+
+    let mut html = String::from("<h2>Response times by rate</h2>\n");
+
+    for (key, head) in next.iter() {
+        let (query_name, endpoint, batch) = key;
+
+        let query = match endpoint {
+            Some(endpoint) => format!("{} @{}", query_name, endpoint),
+            None => query_name.clone(),
+        };
+
+        let title = match batch {
+            Some(batch) => format!("{} (batch of {})", query, batch),
+            None => query,
+        };
+
+        let mut series = latency_series(head, ("head p50", "head p99"), HEAD_COLOR);
+
+        if let Some(base) = previous.get(key) {
+            series.extend(latency_series(base, ("base p50", "base p99"), BASE_COLOR));
+        }
+
+        let ticks: Vec<(f64, String)> = head
+            .iter()
+            .map(|s| (s.rps() as f64, format!("{} rps", s.rps())))
+            .collect();
+
+        html.push_str(&line_chart(&title, "ms", &ticks, &series));
+    }
+
+    html
+}
+
+fn changes_section(differences: &[(&ResponseSamples, Option<QueryComparison>)]) -> String {
+    let mut html = String::from("<h2>Changes</h2>\n");
+
+    let percentiles: [(&str, fn(&QueryComparison) -> Change); 3] =
+        [("p50", |c| c.p50), ("p95", |c| c.p95), ("p99", |c| c.p99)];
+
+    for (name, percentile) in percentiles.iter() {
+        let bars: Vec<Bar> = differences
+            .iter()
+            .filter_map(|(samples, comparison)| {
+                comparison.as_ref().map(|comparison| {
+                    let change = percentile(comparison);
+
+                    Bar {
+                        label: samples.label(),
+                        value: change.change,
+                        color: verdict_color(change.verdict),
+                    }
+                })
+            })
+            .collect();
+
+        if !bars.is_empty() {
+            html.push_str(&bar_chart(&format!("{} change", name), "%", &bars));
+        }
+    }
+
+    html
+}
+
+fn errors_section(summary: &ResponseSummary) -> String {
+    let mut bars = Vec::new();
+
+    for next in summary.next_samples() {
+        if let Some(previous) = summary.previous(next) {
+            bars.push(Bar {
+                label: format!("{} base", next.label()),
+                value: previous.error_rate(),
+                color: BASE_COLOR,
+            });
+        }
+
+        bars.push(Bar {
+            label: format!("{} head", next.label()),
+            value: next.error_rate(),
+            color: HEAD_COLOR,
+        });
+    }
+
+    let mut html = String::from("<h2>Error rates</h2>\n");
+    html.push_str(&bar_chart("Failed requests", "%", &bars));
+    html
+}
+
+fn trends_section(trends: &TrendSummary) -> String {
+    let commits = trends.commits();
+    let every = (commits.len() / 10).max(1);
+
+    let ticks: Vec<(f64, String)> = commits
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % every == 0)
+        .map(|(i, commit)| (i as f64, commit.chars().take(7).collect()))
+        .collect();
+
+    let mut html = format!(
+        "<h2>Trends</h2>\n<p>The last {} commits, oldest first.</p>\n",
+        commits.len()
+    );
+
+    for trend in trends.trends() {
+        let points = |percentile: fn(&(f64, f64, f64)) -> f64| -> Vec<(f64, f64)> {
+            trend
+                .points()
+                .iter()
+                .enumerate()
+                .filter_map(|(i, point)| point.as_ref().map(|p| (i as f64, millis(percentile(p)))))
+                .collect()
+        };
+
+        let series = [
+            Series {
+                name: "p50",
+                color: HEAD_COLOR,
+                dashed: false,
+                points: points(|p| p.0),
+            },
+            Series {
+                name: "p95",
+                color: BASE_COLOR,
+                dashed: false,
+                points: points(|p| p.1),
+            },
+            Series {
+                name: "p99",
+                color: HEAD_COLOR,
+                dashed: true,
+                points: points(|p| p.2),
+            },
+        ];
+
+        html.push_str(&line_chart(&trend.label(), "ms", &ticks, &series));
+    }
+
+    html
+}
+
+pub async fn generate(opts: HtmlReportOpt) -> crate::Result<()> {
+    let summary = ResponseSummary::aggregate(
+        &opts.secondary_storage,
+        opts.connector,
+        &opts.selection.selection(),
+    )
+    .await?;
+
+    let trends =
+        TrendSummary::aggregate(&opts.secondary_storage, opts.connector, opts.commits).await?;
+
+    let (previous_id, next_id) = summary.commits();
+    let differences = summary.differences();
+
+    let mut html = String::new();
+
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Benchmark report: {connector}</title>\n<style>{style}</style>\n</head>\n<body>",
+        connector = opts.connector.as_str(),
+        style = STYLE
+    )
+    .unwrap();
+
+    writeln!(
+        html,
+        "<h1>Benchmark report: {}</h1>\n<p>Comparing <code>{}</code> (base) to <code>{}</code> (head). Generated {}.</p>",
+        opts.connector.as_str(),
+        escape(previous_id),
+        escape(next_id),
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    )
+    .unwrap();
+
+    html.push_str(&changes_section(&differences));
+    html.push_str(&latency_section(&summary));
+    html.push_str(&errors_section(&summary));
+    html.push_str(&trends_section(&trends));
+    html.push_str("</body>\n</html>\n");
+
+    fs::write(&opts.out, html)?;
+    println!("Wrote the report to {}", opts.out.display());
+
+    Ok(())
+}
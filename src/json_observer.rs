@@ -1,4 +1,4 @@
-use crate::requester::ServerInfo;
+use crate::{histogram, requester::ServerInfo};
 use chrono::{DateTime, Utc};
 use hdrhistogram::Histogram;
 use metrics_core::{Drain, Key, Observer};
@@ -11,6 +11,8 @@ pub struct JsonObserver {
     successes: u64,
     failures: u64,
     rps: u64,
+    batch: Option<u64>,
+    endpoint: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,11 +24,31 @@ pub struct ResponseTime {
     p50: u64,
     p95: u64,
     p99: u64,
+    #[serde(default)]
+    p90: u64,
+    #[serde(default)]
+    p999: u64,
+    #[serde(default)]
+    min: u64,
+    #[serde(default)]
+    max: u64,
+    #[serde(default)]
+    mean: f64,
+    #[serde(default)]
+    stddev: f64,
+    /// All response times as a V2 deflate serialized, base64 encoded HDR
+    /// histogram. `None` if it could not be serialized.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    histogram: Option<String>,
     rps: u64,
     successes: u64,
     failures: u64,
     time: String,
     version: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    batch: Option<u64>,
 }
 
 impl ResponseTime {
@@ -58,6 +80,35 @@ impl ResponseTime {
         self.p99
     }
 
+    pub fn p90(&self) -> u64 {
+        self.p90
+    }
+
+    pub fn p999(&self) -> u64 {
+        self.p999
+    }
+
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.stddev
+    }
+
+    /// The encoded histogram of all response times.
+    pub fn histogram(&self) -> Option<&str> {
+        self.histogram.as_ref().map(|s| s.as_str())
+    }
+
     pub fn rps(&self) -> u64 {
         self.rps
     }
@@ -70,6 +121,16 @@ impl ResponseTime {
         self.failures
     }
 
+    /// The number of queries in one request, for batch runs.
+    pub fn batch(&self) -> Option<u64> {
+        self.batch
+    }
+
+    /// The name of the endpoint, when comparing several of them.
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_ref().map(|s| s.as_str())
+    }
+
     pub fn time(&self) -> DateTime<Utc> {
         DateTime::parse_from_rfc3339(&self.time)
             .unwrap()
@@ -78,15 +139,23 @@ impl ResponseTime {
 }
 
 impl JsonObserver {
-    pub fn new<S>(server_info: ServerInfo, query_name: S, rps: u64) -> Self
+    pub fn new<S>(
+        server_info: ServerInfo,
+        query_name: S,
+        rps: u64,
+        batch: Option<u64>,
+        endpoint: Option<String>,
+    ) -> Self
     where
         S: Into<String>,
     {
         Self {
             server_info,
             rps,
+            batch,
+            endpoint,
             query_name: query_name.into(),
-            response_times: Histogram::new(3).unwrap(),
+            response_times: histogram::new(),
             successes: 0,
             failures: 0,
         }
@@ -107,7 +176,7 @@ impl Observer for JsonObserver {
     fn observe_histogram(&mut self, key: Key, values: &[u64]) {
         if key.name().as_ref() == "response_time" {
             for value in values {
-                self.response_times.record(*value).unwrap();
+                histogram::record(&mut self.response_times, *value);
             }
         }
     }
@@ -115,6 +184,20 @@ impl Observer for JsonObserver {
 
 impl Drain<ResponseTime> for JsonObserver {
     fn drain(&mut self) -> ResponseTime {
+        let (mean, stddev) = if self.response_times.len() > 0 {
+            (self.response_times.mean(), self.response_times.stdev())
+        } else {
+            (0.0, 0.0)
+        };
+
+        let histogram = match histogram::encode(&self.response_times) {
+            Ok(histogram) => Some(histogram),
+            Err(e) => {
+                eprintln!("Storing {} without its histogram: {}", self.query_name, e);
+                None
+            }
+        };
+
         ResponseTime {
             commit: self.server_info.commit.clone(),
             connector: self.server_info.primary_connector.clone(),
@@ -123,10 +206,19 @@ impl Drain<ResponseTime> for JsonObserver {
             p50: self.response_times.value_at_quantile(0.5),
             p95: self.response_times.value_at_quantile(0.95),
             p99: self.response_times.value_at_quantile(0.99),
+            p90: self.response_times.value_at_quantile(0.9),
+            p999: self.response_times.value_at_quantile(0.999),
+            min: self.response_times.min(),
+            max: self.response_times.max(),
+            mean,
+            stddev,
+            histogram,
             rps: self.rps,
             successes: self.successes,
             failures: self.failures,
             time: Utc::now().to_rfc3339(),
+            endpoint: self.endpoint.clone(),
+            batch: self.batch,
         }
     }
 }
@@ -1,22 +1,36 @@
 mod bar;
 mod bench;
+mod check;
 mod config;
 mod console_observer;
+mod elastic_mapping;
 mod error;
+mod export;
+mod histogram;
+mod hook;
+mod html_report;
 mod json_observer;
 mod kibana;
 mod metrics_sender;
+mod metrics_sink;
 mod metrics_storage;
+mod migrations;
+mod prometheus;
 mod reporter;
 mod requester;
 mod response_summary;
 mod server;
+mod significance;
+mod time_series;
+mod trend;
 
 use bench::Bench;
-use reporter::{Reporter, SlackReporter, StdoutReporter};
-use response_summary::ConnectorType;
+use reporter::{
+    BatchReporter, JUnitReporter, MarkdownReporter, Reporter, SlackReporter, StdoutReporter,
+};
+use response_summary::{ConnectorType, Selection};
 use server::Server;
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 use structopt::StructOpt;
 
 type Result<T> = std::result::Result<T, error::Error>;
@@ -35,21 +49,75 @@ pub struct BenchOpt {
     /// Show fancy progress metrics (disable for CI)
     #[structopt(long)]
     show_progress: bool,
-    /// Which Elastic Search database to write
+    /// Which Elastic Search database to write, if any
     #[structopt(long)]
-    metrics_database: String,
+    metrics_database: Option<String>,
     /// The GraphQL endpoint type. (prisma|hasura)
     #[structopt(long)]
     endpoint_type: Option<requester::EndpointType>,
     /// Path to the local secondary database
     #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
     secondary_storage: String,
+    #[structopt(flatten)]
+    elastic: ElasticOpt,
+    /// Where to keep results Elastic Search could not receive
+    #[structopt(long, default_value = "elastic-spool.ndjson")]
+    elastic_spool: PathBuf,
+    /// Run the whole benchmark this many times, storing every repetition
+    /// as its own run
+    #[structopt(long, default_value = "1")]
+    repeat: u32,
+    /// Measure a commit again even if results for it are already stored
+    #[structopt(long)]
+    allow_remeasure: bool,
+    /// Only warn if a sink cannot store the results, e.g. an index is
+    /// missing, instead of stopping before the benchmark
+    #[structopt(long)]
+    skip_sink_verification: bool,
+    /// Serve live metrics in the Prometheus format at this address while
+    /// benchmarking, e.g. 0.0.0.0:9898
+    #[structopt(long)]
+    metrics_listen: Option<SocketAddr>,
+    /// Also write every result to a CSV or JSON Lines file, e.g. results.csv
+    #[structopt(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ElasticOpt {
     /// Username to the ElasticSearch database
     #[structopt(long, env = "ELASTIC_USER")]
-    elastic_user: String,
+    elastic_user: Option<String>,
     /// Password to the ElasticSearch database
     #[structopt(long, env = "ELASTIC_PW")]
-    elastic_password: String,
+    elastic_password: Option<String>,
+    /// API key to the ElasticSearch database, instead of user and password
+    #[structopt(long, env = "ELASTIC_API_KEY")]
+    elastic_api_key: Option<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ElasticInitOpt {
+    /// The Elastic Search URL
+    #[structopt(long)]
+    elastic_endpoint: String,
+    /// The index to create, the per-second series go to `<index>-series`
+    #[structopt(long)]
+    index: String,
+    #[structopt(flatten)]
+    elastic: ElasticOpt,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct FlushSpoolOpt {
+    /// The spool file written by the benchmarks
+    #[structopt(long, default_value = "elastic-spool.ndjson")]
+    elastic_spool: PathBuf,
+    /// The test file declaring the sinks, for their credentials
+    #[structopt(long)]
+    query_file: Option<String>,
+    #[structopt(flatten)]
+    elastic: ElasticOpt,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -76,6 +144,80 @@ pub struct SetupOpt {
     passphrase: Option<String>,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+pub struct SelectionOpt {
+    /// Report on this run instead of the latest measurements
+    #[structopt(long)]
+    run: Option<i64>,
+    /// Compare against this run (run:<id>), commit (or its prefix) or version name
+    #[structopt(long)]
+    base: Option<String>,
+    /// Report on this run (run:<id>), commit (or its prefix) or version name
+    #[structopt(long)]
+    head: Option<String>,
+    /// Changes smaller than this, in percent, are reported as inconclusive
+    #[structopt(long, default_value = "5")]
+    noise_threshold: f64,
+}
+
+impl SelectionOpt {
+    fn selection(&self) -> Selection {
+        Selection {
+            base: self.base.clone(),
+            head: self.head.clone(),
+            run: self.run,
+            noise_threshold: self.noise_threshold,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct CheckOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The connector to check (postgres|mysql)
+    #[structopt(long)]
+    connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
+    #[structopt(flatten)]
+    budget: BudgetOpt,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct BudgetOpt {
+    /// The largest allowed p50 regression, in percent
+    #[structopt(long)]
+    p50: Option<f64>,
+    /// The largest allowed p95 regression, in percent
+    #[structopt(long)]
+    p95: Option<f64>,
+    /// The largest allowed p99 regression, in percent
+    #[structopt(long)]
+    p99: Option<f64>,
+    /// A toml file with the allowed regressions per query
+    #[structopt(long)]
+    budget: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct JUnitReportOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The connector to get the reports from (postgres|mysql)
+    #[structopt(long)]
+    connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
+    #[structopt(flatten)]
+    budget: BudgetOpt,
+    /// Write the report to this file instead of the console
+    #[structopt(long)]
+    output: Option<PathBuf>,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct StdoutReportOpt {
     /// Path to the local secondary database
@@ -84,6 +226,8 @@ pub struct StdoutReportOpt {
     /// The connector to get the reports from (postgres|mysql)
     #[structopt(long)]
     connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -97,6 +241,79 @@ pub struct SlackReportOpt {
     /// The connector to get the reports from (postgres|mysql)
     #[structopt(long)]
     connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct MarkdownReportOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The connector to get the reports from (postgres|mysql)
+    #[structopt(long)]
+    connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
+    /// Write the report to this file instead of the console
+    #[structopt(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct HtmlReportOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The connector to get the reports from (postgres|mysql)
+    #[structopt(long)]
+    connector: ConnectorType,
+    #[structopt(flatten)]
+    selection: SelectionOpt,
+    /// The HTML file to write
+    #[structopt(long, default_value = "report.html")]
+    out: PathBuf,
+    /// How many of the latest commits the trends show
+    #[structopt(long, default_value = "10")]
+    commits: usize,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct TrendOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The connector to follow (postgres|mysql)
+    #[structopt(long)]
+    connector: ConnectorType,
+    /// How many of the latest commits to show
+    #[structopt(long, default_value = "20")]
+    commits: usize,
+    /// Only show this query
+    #[structopt(long)]
+    query: Option<String>,
+    /// Steps smaller than this, in percent, are not flagged
+    #[structopt(long, default_value = "10")]
+    noise_threshold: f64,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ExportOpt {
+    /// Path to the local secondary database
+    #[structopt(long, default_value = "file:metrics.db", env = "SECONDARY_STORAGE")]
+    secondary_storage: String,
+    /// The file to write, either .csv or .json/.jsonl (one JSON object per line)
+    #[structopt(long)]
+    output: PathBuf,
+    /// Only export this commit, or the commits starting with it
+    #[structopt(long)]
+    commit: Option<String>,
+    /// Only export this connector (postgres|mysql)
+    #[structopt(long)]
+    connector: Option<ConnectorType>,
+    /// Only export this query
+    #[structopt(long)]
+    query: Option<String>,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -112,6 +329,24 @@ pub enum Opt {
     StdoutReport(StdoutReportOpt),
     /// Send last report statistics to Slack
     SlackReport(SlackReportOpt),
+    /// Write last report statistics as a markdown table
+    MarkdownReport(MarkdownReportOpt),
+    /// Write last report statistics as JUnit XML, one test per query and rate
+    JunitReport(JUnitReportOpt),
+    /// Write last report statistics with charts to a static HTML file
+    HtmlReport(HtmlReportOpt),
+    /// Print per-item response times of the last measurement by batch size
+    BatchReport(StdoutReportOpt),
+    /// Print the response times of every query over the latest commits
+    Trend(TrendOpt),
+    /// Fail on regressions beyond the allowed budget, for CI
+    Check(CheckOpt),
+    /// Export stored results to a CSV or JSON file
+    Export(ExportOpt),
+    /// Create the Elastic Search indices with their mappings
+    ElasticInit(ElasticInitOpt),
+    /// Deliver results spooled while Elastic Search was unreachable
+    FlushSpool(FlushSpoolOpt),
 }
 
 #[tokio::main]
@@ -124,13 +359,67 @@ async fn main() -> Result<()> {
         Opt::Setup(setup_opts) => Server::new(setup_opts)?.setup(),
         Opt::StdoutReport(report_opts) => {
             StdoutReporter
-                .report(&report_opts.secondary_storage, report_opts.connector)
+                .report(
+                    &report_opts.secondary_storage,
+                    report_opts.connector,
+                    &report_opts.selection.selection(),
+                )
                 .await
         }
         Opt::SlackReport(report_opts) => {
             SlackReporter::new(&report_opts.webhook_url)
-                .report(&report_opts.secondary_storage, report_opts.connector)
+                .report(
+                    &report_opts.secondary_storage,
+                    report_opts.connector,
+                    &report_opts.selection.selection(),
+                )
+                .await
+        }
+        Opt::MarkdownReport(report_opts) => {
+            MarkdownReporter::new(report_opts.output.clone())
+                .report(
+                    &report_opts.secondary_storage,
+                    report_opts.connector,
+                    &report_opts.selection.selection(),
+                )
+                .await
+        }
+        Opt::BatchReport(report_opts) => {
+            BatchReporter
+                .report(
+                    &report_opts.secondary_storage,
+                    report_opts.connector,
+                    &report_opts.selection.selection(),
+                )
                 .await
         }
+        Opt::JunitReport(report_opts) => {
+            JUnitReporter::new(
+                report_opts.output.clone(),
+                check::Budget::from_opts(&report_opts.budget)?,
+            )
+            .report(
+                &report_opts.secondary_storage,
+                report_opts.connector,
+                &report_opts.selection.selection(),
+            )
+            .await
+        }
+        Opt::HtmlReport(report_opts) => html_report::generate(report_opts).await,
+        Opt::Trend(trend_opts) => trend::trend(trend_opts).await,
+        Opt::Check(check_opts) => check::check(check_opts).await,
+        Opt::Export(export_opts) => export::export(export_opts).await,
+        Opt::ElasticInit(init_opts) => elastic_mapping::init(init_opts).await,
+        Opt::FlushSpool(flush_opts) => {
+            let sinks = match flush_opts.query_file {
+                Some(ref path) => config::sink_configs(path)?,
+                None => Vec::new(),
+            };
+
+            metrics_sender::flush_spool(&flush_opts.elastic_spool, |endpoint, index| {
+                metrics_sink::spooled_auth(&flush_opts.elastic, &sinks, endpoint, index)
+            })
+            .await
+        }
     }
 }
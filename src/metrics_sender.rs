@@ -1,45 +1,658 @@
-use crate::json_observer::ResponseTime;
-use reqwest::Client;
-use std::io::{Error, ErrorKind};
+use crate::{
+    elastic_mapping, json_observer::ResponseTime, metrics_sink::MetricsSink,
+    time_series::SeriesPoint,
+};
+use async_trait::async_trait;
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Client, RequestBuilder, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::delay_for;
+
+/// How many times a bulk request is tried before spooling the documents.
+const ATTEMPTS: u32 = 5;
+
+/// The wait before the first retry, doubled for every following one.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Credentials for Elastic Search.
+#[derive(Debug, Clone)]
+pub enum ElasticAuth {
+    Basic {
+        user: String,
+        password: String,
+    },
+    /// The base64 encoded `id:api_key`, as returned when creating the key.
+    ApiKey(String),
+}
+
+/// A document with the index it goes to.
+type Document = (String, serde_json::Value);
+
+/// The documents a bulk delivery gave up on.
+pub struct Undelivered {
+    /// Documents that may go through later, e.g. once the cluster is back.
+    pub documents: Vec<Document>,
+    /// Why the last attempt failed.
+    pub reason: String,
+    /// The errors of the documents Elastic Search refused for good, such as
+    /// mapping conflicts. Sending them again would not help.
+    pub refused: Vec<serde_json::Value>,
+}
+
+/// The documents of a bulk request Elastic Search did not index.
+#[derive(Default)]
+struct Rejected {
+    /// Positions of the documents rejected for the moment (429 or 5xx),
+    /// with their errors.
+    retry: Vec<(usize, serde_json::Value)>,
+    /// The errors of the documents refused for good.
+    refused: Vec<serde_json::Value>,
+}
+
+/// A document not delivered to Elastic Search, one per line in the spool
+/// file. Credentials are not written to the spool, `flush-spool` finds them
+/// again by endpoint and index.
+#[derive(Debug, Serialize, Deserialize)]
+struct SpooledDocument {
+    endpoint: String,
+    index: String,
+    document: serde_json::Value,
+}
 
 pub struct MetricsSender {
     endpoint: String,
     database: String,
     client: Client,
-    user: String,
-    password: String,
+    auth: ElasticAuth,
+    spool: Option<PathBuf>,
+    backoff: Duration,
 }
 
 impl MetricsSender {
-    pub fn new(endpoint: &str, database: &str, user: &str, password: &str) -> Self {
+    pub fn new(endpoint: &str, database: &str, auth: ElasticAuth) -> Self {
         Self {
             endpoint: endpoint.into(),
             database: database.into(),
-            user: user.into(),
-            password: password.into(),
+            auth,
             client: Client::new(),
+            spool: None,
+            backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Documents that cannot be delivered after retrying are appended to
+    /// the `spool` file, instead of failing.
+    pub fn spool_to(mut self, spool: &Path) -> Self {
+        self.spool = Some(spool.into());
+        self
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth {
+            ElasticAuth::Basic { user, password } => request.basic_auth(user, Some(password)),
+            ElasticAuth::ApiKey(key) => request.header(AUTHORIZATION, format!("ApiKey {}", key)),
         }
     }
 
     pub async fn send(&self, metrics: &ResponseTime) -> crate::Result<()> {
-        let response = self
+        let document = serde_json::to_value(metrics)?;
+        self.deliver(vec![(self.database.clone(), document)]).await
+    }
+
+    /// Sends the per-second buckets of a run to the `<database>-series`
+    /// index, one document per second.
+    pub async fn send_series(&self, series: &[SeriesPoint]) -> crate::Result<()> {
+        let index = format!("{}-series", self.database);
+
+        let documents = series
+            .iter()
+            .map(|point| Ok((index.clone(), serde_json::to_value(point)?)))
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        self.deliver(documents).await
+    }
+
+    /// Sends the documents, spooling them if Elastic Search stays
+    /// unreachable, so the benchmark can go on.
+    async fn deliver(&self, documents: Vec<Document>) -> crate::Result<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let undelivered = match self.send_bulk(documents).await {
+            Ok(()) => return Ok(()),
+            Err(undelivered) => undelivered,
+        };
+
+        if !undelivered.documents.is_empty() {
+            match self.spool {
+                Some(ref spool) => {
+                    println!(
+                        "Could not deliver {} document(s) to Elastic Search, spooling them to {}: {}",
+                        undelivered.documents.len(),
+                        spool.display(),
+                        undelivered.reason
+                    );
+
+                    let mut file = OpenOptions::new().create(true).append(true).open(spool)?;
+
+                    for (index, document) in undelivered.documents {
+                        let spooled = SpooledDocument {
+                            endpoint: self.endpoint.clone(),
+                            index,
+                            document,
+                        };
+
+                        writeln!(file, "{}", serde_json::to_string(&spooled)?)?;
+                    }
+                }
+                None => return Err(Error::new(ErrorKind::Other, undelivered.reason).into()),
+            }
+        }
+
+        match undelivered.refused.first() {
+            Some(error) => Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Elastic Search refused {} document(s): {}",
+                    undelivered.refused.len(),
+                    error
+                ),
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the (index, document) pairs in `_bulk` requests, retrying with
+    /// exponential backoff. Documents Elastic Search already indexed are not
+    /// sent again, only the ones rejected for the moment. Documents refused
+    /// for good are not retried.
+    pub async fn send_bulk(&self, documents: Vec<Document>) -> Result<(), Undelivered> {
+        let mut pending = documents;
+        let mut refused = Vec::new();
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+
+        loop {
+            // Only the reason is kept over the pause, our errors are not `Send`.
+            let reason = match self.try_bulk(&pending).await {
+                Ok(rejected) => {
+                    refused.extend(rejected.refused);
+
+                    if rejected.retry.is_empty() {
+                        pending.clear();
+                    }
+
+                    if pending.is_empty() {
+                        if refused.is_empty() {
+                            return Ok(());
+                        }
+
+                        return Err(Undelivered {
+                            documents: pending,
+                            reason: "documents refused".into(),
+                            refused,
+                        });
+                    }
+
+                    let reason = format!(
+                        "{} of {} document(s) rejected: {}",
+                        rejected.retry.len(),
+                        pending.len(),
+                        rejected.retry[0].1
+                    );
+
+                    pending = pending
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, _)| rejected.retry.iter().any(|(r, _)| r == i))
+                        .map(|(_, document)| document)
+                        .collect();
+
+                    reason
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= ATTEMPTS {
+                return Err(Undelivered {
+                    documents: pending,
+                    reason,
+                    refused,
+                });
+            }
+
+            delay_for(backoff).await;
+
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    /// Creates the index with the given mappings. False if it already
+    /// exists.
+    pub async fn create_index(
+        &self,
+        index: &str,
+        mappings: &serde_json::Value,
+    ) -> crate::Result<bool> {
+        let request = self
             .client
-            .post(&format!("{}/{}/_doc/", self.endpoint, self.database))
-            .basic_auth(&self.user, Some(&self.password))
-            .json(metrics)
-            .send()
-            .await?;
+            .put(&format!("{}/{}", self.endpoint, index))
+            .json(mappings);
+
+        let response = self.authenticate(request).send().await?;
+        let status = response.status();
+        let json: serde_json::Value = response.json().await?;
 
-        if response.status().is_success() {
+        if status.is_success() {
+            Ok(true)
+        } else if json["error"]["type"] == "resource_already_exists_exception" {
+            Ok(false)
+        } else {
+            Err(Error::new(
+                ErrorKind::Other,
+                format!("Failed to create index {}: {}", index, json),
+            )
+            .into())
+        }
+    }
+
+    /// Fails if the index does not exist, or maps the fields of our
+    /// documents to other types.
+    pub async fn verify_index(&self, index: &str) -> crate::Result<()> {
+        let incompatible = |reason: String| crate::error::Error::IncompatibleIndex {
+            index: index.into(),
+            reason,
+        };
+
+        let request = self
+            .client
+            .get(&format!("{}/{}/_mapping", self.endpoint, index));
+
+        let response = self.authenticate(request).send().await?;
+        let status = response.status();
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(incompatible(
+                "the index does not exist, create it with `chihiro elastic-init`".into(),
+            ));
+        }
+
+        let json: serde_json::Value = response.json().await?;
+
+        if !status.is_success() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Failed to read the mappings of {}: {}", index, json),
+            )
+            .into());
+        }
+
+        // Keyed by the concrete index name, which differs for aliases.
+        let properties = json
+            .as_object()
+            .and_then(|indices| indices.values().next())
+            .map(|index| index["mappings"]["properties"].clone())
+            .unwrap_or_default();
+
+        let problems = elastic_mapping::incompatibilities(index, &properties);
+
+        if problems.is_empty() {
             Ok(())
         } else {
-            let json: serde_json::Value = response.json().await?;
+            Err(incompatible(problems.join(", ")))
+        }
+    }
 
-            Err(Error::new(
+    /// Sends the documents in one `_bulk` request, returning the ones
+    /// Elastic Search did not index.
+    async fn try_bulk(&self, documents: &[Document]) -> crate::Result<Rejected> {
+        let mut body = String::new();
+
+        for (index, document) in documents {
+            body.push_str(&serde_json::json!({ "index": { "_index": index } }).to_string());
+            body.push('\n');
+            body.push_str(&document.to_string());
+            body.push('\n');
+        }
+
+        let request = self
+            .client
+            .post(&format!("{}/_bulk", self.endpoint))
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body);
+
+        let response = self.authenticate(request).send().await?;
+        let status = response.status();
+        let json: serde_json::Value = response.json().await?;
+
+        if !status.is_success() {
+            return Err(Error::new(
                 ErrorKind::Other,
                 format!("Failed to send metrics: {}", json),
             )
-            .into())
+            .into());
+        }
+
+        let mut rejected = Rejected::default();
+
+        if json["errors"] != serde_json::Value::Bool(true) {
+            return Ok(rejected);
+        }
+
+        let items = match json["items"].as_array() {
+            Some(items) => items,
+            None => {
+                rejected.retry = (0..documents.len()).map(|i| (i, json.clone())).collect();
+                return Ok(rejected);
+            }
+        };
+
+        for (i, result) in items.iter().map(|item| &item["index"]).enumerate() {
+            match result["status"].as_u64() {
+                Some(status) if status < 300 && result["error"].is_null() => (),
+                // Too many requests or a server error, worth another try.
+                Some(status) if status == 429 || status >= 500 => {
+                    rejected.retry.push((i, result["error"].clone()))
+                }
+                Some(_) => rejected.refused.push(result["error"].clone()),
+                None => rejected.retry.push((i, result["error"].clone())),
+            }
+        }
+
+        Ok(rejected)
+    }
+}
+
+/// Re-delivers the spooled documents, keeping the ones that fail again in
+/// the spool. `auth` gives the credentials for an endpoint and index.
+pub async fn flush_spool<F>(spool: &Path, auth: F) -> crate::Result<()>
+where
+    F: Fn(&str, &str) -> crate::Result<ElasticAuth>,
+{
+    let contents = match fs::read_to_string(spool) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {
+            println!("Nothing to flush, {} does not exist", spool.display());
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Grouped by endpoint and index, as the credentials may differ.
+    let mut groups: Vec<((String, String), Vec<Document>)> = Vec::new();
+
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        let spooled: SpooledDocument = serde_json::from_str(line)?;
+        let key = (spooled.endpoint, spooled.index.clone());
+
+        match groups.iter_mut().find(|(k, _)| k == &key) {
+            Some((_, documents)) => documents.push((spooled.index, spooled.document)),
+            None => groups.push((key, vec![(spooled.index, spooled.document)])),
+        }
+    }
+
+    let mut remaining = String::new();
+    let mut delivered = 0;
+
+    for ((endpoint, index), documents) in groups {
+        let total = documents.len();
+
+        let undelivered = match auth(&endpoint, &index) {
+            Ok(auth) => {
+                let sender = MetricsSender::new(&endpoint, &index, auth);
+
+                match sender.send_bulk(documents).await {
+                    Ok(()) => Vec::new(),
+                    Err(undelivered) => {
+                        for error in undelivered.refused.iter() {
+                            println!(
+                                "Dropping a document {}/{} refused: {}",
+                                endpoint, index, error
+                            );
+                        }
+
+                        if !undelivered.documents.is_empty() {
+                            println!(
+                                "Could not deliver to {}/{}: {}",
+                                endpoint, index, undelivered.reason
+                            );
+                        }
+
+                        undelivered.documents
+                    }
+                }
+            }
+            Err(e) => {
+                println!("No credentials for {}/{}: {}", endpoint, index, e);
+                documents
+            }
+        };
+
+        delivered += total - undelivered.len();
+
+        for (index, document) in undelivered {
+            let spooled = SpooledDocument {
+                endpoint: endpoint.clone(),
+                index,
+                document,
+            };
+
+            remaining.push_str(&serde_json::to_string(&spooled)?);
+            remaining.push('\n');
         }
     }
+
+    if remaining.is_empty() {
+        fs::remove_file(spool)?;
+    } else {
+        fs::write(spool, remaining)?;
+    }
+
+    println!("Delivered {} spooled document(s)", delivered);
+
+    Ok(())
+}
+
+#[async_trait]
+impl MetricsSink for MetricsSender {
+    async fn verify(&self) -> crate::Result<()> {
+        self.verify_index(&self.database).await?;
+        self.verify_index(&format!("{}-series", self.database))
+            .await
+    }
+
+    async fn store(&self, metrics: &ResponseTime) -> crate::Result<()> {
+        self.send(metrics).await
+    }
+
+    async fn store_series(&self, series: &[SeriesPoint]) -> crate::Result<()> {
+        self.send_series(series).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use serde_json::json;
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    type Responses = Arc<Mutex<VecDeque<(u16, serde_json::Value)>>>;
+    type Bodies = Arc<Mutex<Vec<String>>>;
+
+    /// A local server answering the bulk requests with the given responses
+    /// in order, and `200 OK` once they run out. Records the request bodies.
+    fn mock(responses: Vec<(u16, serde_json::Value)>) -> (String, Bodies) {
+        let responses: Responses = Arc::new(Mutex::new(responses.into_iter().collect()));
+        let bodies: Bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+
+        let make_service = make_service_fn(move |_| {
+            let responses = responses.clone();
+            let bodies = bodies.clone();
+
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                    let responses = responses.clone();
+                    let bodies = bodies.clone();
+
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await?;
+                        bodies
+                            .lock()
+                            .unwrap()
+                            .push(String::from_utf8_lossy(&body).into_owned());
+
+                        let (status, json) = responses
+                            .lock()
+                            .unwrap()
+                            .pop_front()
+                            .unwrap_or((200, json!({ "errors": false, "items": [] })));
+
+                        Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(status)
+                                .header(CONTENT_TYPE, "application/json")
+                                .body(Body::from(json.to_string()))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let endpoint = format!("http://{}", server.local_addr());
+
+        tokio::spawn(server);
+
+        (endpoint, recorded)
+    }
+
+    fn sender(endpoint: &str) -> MetricsSender {
+        let mut sender = MetricsSender::new(endpoint, "results", ElasticAuth::ApiKey("key".into()));
+        sender.backoff = Duration::from_millis(1);
+        sender
+    }
+
+    fn documents(count: usize) -> Vec<Document> {
+        (0..count)
+            .map(|i| {
+                (
+                    "results".to_string(),
+                    json!({ "query_name": format!("q{}", i) }),
+                )
+            })
+            .collect()
+    }
+
+    fn rejected_item() -> serde_json::Value {
+        json!({ "index": { "status": 429, "error": { "type": "es_rejected_execution_exception" } } })
+    }
+
+    fn refused_item() -> serde_json::Value {
+        json!({ "index": { "status": 400, "error": { "type": "mapper_parsing_exception" } } })
+    }
+
+    fn indexed_item() -> serde_json::Value {
+        json!({ "index": { "status": 201 } })
+    }
+
+    #[tokio::test]
+    async fn delivers_documents_in_one_bulk_request() {
+        let (endpoint, bodies) = mock(vec![]);
+
+        assert!(sender(&endpoint).send_bulk(documents(2)).await.is_ok());
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(1, bodies.len());
+        assert_eq!(4, bodies[0].lines().count());
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (endpoint, bodies) = mock(vec![(503, json!({ "error": "unavailable" }))]);
+
+        assert!(sender(&endpoint).send_bulk(documents(1)).await.is_ok());
+        assert_eq!(2, bodies.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn resends_only_the_rejected_documents() {
+        let partial = json!({ "errors": true, "items": [indexed_item(), rejected_item()] });
+        let (endpoint, bodies) = mock(vec![(200, partial)]);
+
+        assert!(sender(&endpoint).send_bulk(documents(2)).await.is_ok());
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(2, bodies.len());
+        assert!(!bodies[1].contains("\"q0\""));
+        assert!(bodies[1].contains("\"q1\""));
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_or_spool_refused_documents() {
+        let spool =
+            std::env::temp_dir().join(format!("chihiro-refused-{}.ndjson", std::process::id()));
+        let _ = fs::remove_file(&spool);
+
+        let partial = json!({ "errors": true, "items": [indexed_item(), refused_item()] });
+        let (endpoint, bodies) = mock(vec![(200, partial)]);
+
+        let sender = sender(&endpoint).spool_to(&spool);
+        assert!(sender.deliver(documents(2)).await.is_err());
+
+        assert_eq!(1, bodies.lock().unwrap().len());
+        assert!(!spool.exists());
+    }
+
+    #[tokio::test]
+    async fn spools_undelivered_documents_and_flushes_them() {
+        let spool =
+            std::env::temp_dir().join(format!("chihiro-spool-{}.ndjson", std::process::id()));
+        let _ = fs::remove_file(&spool);
+
+        let partial = json!({ "errors": true, "items": [indexed_item(), rejected_item()] });
+        let mut responses = vec![(200, partial)];
+        responses.extend((1..ATTEMPTS).map(|_| (500, json!({ "error": "down" }))));
+
+        let (endpoint, bodies) = mock(responses);
+
+        let sender = sender(&endpoint).spool_to(&spool);
+        assert!(sender.deliver(documents(2)).await.is_ok());
+
+        let spooled = fs::read_to_string(&spool).unwrap();
+        assert_eq!(1, spooled.lines().count());
+        assert!(spooled.contains("\"q1\""));
+
+        flush_spool(&spool, |_, index| {
+            assert_eq!("results", index);
+            Ok(ElasticAuth::ApiKey("key".into()))
+        })
+        .await
+        .unwrap();
+
+        assert!(!spool.exists());
+
+        let bodies = bodies.lock().unwrap();
+        assert!(bodies.last().unwrap().contains("\"q1\""));
+        assert!(!bodies.last().unwrap().contains("\"q0\""));
+    }
 }
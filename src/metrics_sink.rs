@@ -0,0 +1,202 @@
+use crate::{
+    config::{QueryConfig, SinkConfig},
+    error::Error,
+    export::FileSink,
+    json_observer::ResponseTime,
+    metrics_sender::{ElasticAuth, MetricsSender},
+    metrics_storage::MetricsStorage,
+    requester::ServerInfo,
+    time_series::SeriesPoint,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// What a benchmark invocation measures, recorded before its first result.
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    /// The id the secondary storage gave the run, set while starting it.
+    pub id: Option<i64>,
+    pub identifier: String,
+    /// The test configuration with its includes merged in, before
+    /// environment variables are resolved.
+    pub config: String,
+    pub endpoint_url: String,
+    pub endpoint_type: String,
+    pub host: String,
+    /// The default duration of a single test, in seconds.
+    pub duration: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    Completed,
+    Failed,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// A destination for the results of the benchmark runs.
+#[async_trait]
+pub trait MetricsSink: Send + Sync {
+    /// Fails if results cannot be stored, checked before benchmarking.
+    async fn verify(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// True if results for this server version are already stored.
+    async fn contains(&self, _info: &ServerInfo) -> crate::Result<bool> {
+        Ok(false)
+    }
+
+    /// Records the start of a benchmark run. Results stored afterwards
+    /// belong to it. The id of the run, if the sink assigns one.
+    async fn start_run(&self, _run: &RunInfo) -> crate::Result<Option<i64>> {
+        Ok(None)
+    }
+
+    /// Records how the current run ended.
+    async fn finish_run(&self, _status: RunStatus) -> crate::Result<()> {
+        Ok(())
+    }
+
+    async fn store(&self, metrics: &ResponseTime) -> crate::Result<()>;
+
+    /// Stores the per-second buckets of a run. Sinks without a place for
+    /// them skip the series.
+    async fn store_series(&self, _series: &[SeriesPoint]) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds the sinks declared in the configuration. Without a declaration,
+/// results go to the secondary storage, and to Elastic Search if a metrics
+/// database is given on the command line. An `--output` file is written in
+/// both cases.
+pub async fn from_config(
+    opts: &crate::BenchOpt,
+    query_config: &QueryConfig,
+) -> crate::Result<Vec<Box<dyn MetricsSink>>> {
+    let mut sinks: Vec<Box<dyn MetricsSink>> = Vec::new();
+
+    match query_config.sinks() {
+        Some(configs) => {
+            for config in configs {
+                match config {
+                    SinkConfig::Elastic {
+                        endpoint,
+                        index,
+                        user,
+                        password,
+                        api_key,
+                    } => {
+                        let auth = elastic_auth(
+                            &opts.elastic,
+                            user.as_ref().map(|s| s.as_str()),
+                            password.as_ref().map(|s| s.as_str()),
+                            api_key.as_ref().map(|s| s.as_str()),
+                        )?;
+
+                        sinks.push(Box::new(
+                            MetricsSender::new(endpoint, index, auth).spool_to(&opts.elastic_spool),
+                        ));
+                    }
+                    SinkConfig::Database { url } => {
+                        sinks.push(Box::new(MetricsStorage::new(url).await?));
+                    }
+                }
+            }
+        }
+        None => {
+            sinks.push(Box::new(
+                MetricsStorage::new(&opts.secondary_storage).await?,
+            ));
+
+            if let Some(ref database) = opts.metrics_database {
+                let endpoint = query_config.elastic_endpoint().ok_or_else(|| {
+                    Error::InvalidConfig(
+                        "a metrics database is given, but no `elastic_endpoint`".into(),
+                    )
+                })?;
+
+                let auth = elastic_auth(&opts.elastic, None, None, None)?;
+
+                sinks.push(Box::new(
+                    MetricsSender::new(endpoint, database, auth).spool_to(&opts.elastic_spool),
+                ));
+            }
+        }
+    }
+
+    if let Some(ref output) = opts.output {
+        sinks.push(Box::new(FileSink::new(output, query_config.identifier())?));
+    }
+
+    Ok(sinks)
+}
+
+/// The credentials for documents spooled for an Elastic Search endpoint and
+/// index: the ones of the matching `[[sink]]`, falling back to the command
+/// line.
+pub fn spooled_auth(
+    opts: &crate::ElasticOpt,
+    sinks: &[SinkConfig],
+    endpoint: &str,
+    index: &str,
+) -> crate::Result<ElasticAuth> {
+    let sink = sinks.iter().find_map(|sink| match sink {
+        SinkConfig::Elastic {
+            endpoint: sink_endpoint,
+            index: sink_index,
+            user,
+            password,
+            api_key,
+        } if sink_endpoint == endpoint
+            && (sink_index == index || format!("{}-series", sink_index) == index) =>
+        {
+            Some((user, password, api_key))
+        }
+        _ => None,
+    });
+
+    match sink {
+        Some((user, password, api_key)) => elastic_auth(
+            opts,
+            user.as_ref().map(|s| s.as_str()),
+            password.as_ref().map(|s| s.as_str()),
+            api_key.as_ref().map(|s| s.as_str()),
+        ),
+        None => elastic_auth(opts, None, None, None),
+    }
+}
+
+/// The credentials given in the configuration, falling back to the ones
+/// from the command line.
+pub fn elastic_auth(
+    opts: &crate::ElasticOpt,
+    user: Option<&str>,
+    password: Option<&str>,
+    api_key: Option<&str>,
+) -> crate::Result<ElasticAuth> {
+    let api_key = api_key.or_else(|| opts.elastic_api_key.as_ref().map(|s| s.as_str()));
+    let user = user.or_else(|| opts.elastic_user.as_ref().map(|s| s.as_str()));
+    let password = password.or_else(|| opts.elastic_password.as_ref().map(|s| s.as_str()));
+
+    match (api_key, user, password) {
+        (Some(api_key), _, _) => Ok(ElasticAuth::ApiKey(api_key.into())),
+        (None, Some(user), Some(password)) => Ok(ElasticAuth::Basic {
+            user: user.into(),
+            password: password.into(),
+        }),
+        _ => Err(Error::InvalidConfig(
+            "Elastic Search needs either an API key or a user and a password".into(),
+        )),
+    }
+}
@@ -1,17 +1,79 @@
-use crate::{json_observer::ResponseTime, requester::ServerInfo};
+use crate::{
+    error::Error,
+    json_observer::ResponseTime,
+    metrics_sink::{MetricsSink, RunInfo, RunStatus},
+    migrations,
+    requester::ServerInfo,
+    time_series::SeriesPoint,
+};
+use async_trait::async_trait;
+use chrono::Utc;
 use quaint::{prelude::*, single::Quaint};
+use std::sync::Mutex;
 
 pub struct MetricsStorage {
     db: Quaint,
+    /// The id of the current run, if one was started.
+    run: Mutex<Option<i64>>,
 }
 
 impl MetricsStorage {
+    /// Connects to the database, creating or upgrading its schema as
+    /// needed.
     pub async fn new(uri: &str) -> crate::Result<Self> {
+        let db = Quaint::new(uri).await?;
+        migrations::run(&db, uri).await?;
+
         Ok(Self {
-            db: Quaint::new(uri).await?,
+            db,
+            run: Mutex::new(None),
         })
     }
 
+    fn current_run(&self) -> Option<i64> {
+        *self.run.lock().unwrap()
+    }
+
+    /// Records the run, returning its id.
+    pub async fn start_run(&self, run: &RunInfo) -> crate::Result<i64> {
+        let insert = Insert::single_into("run")
+            .value("identifier", run.identifier.as_str())
+            .value("config", run.config.as_str())
+            .value("endpoint_url", run.endpoint_url.as_str())
+            .value("endpoint_type", run.endpoint_type.as_str())
+            .value("host", run.host.as_str())
+            .value("duration", run.duration as i64)
+            .value("started_at", run.started_at)
+            .value("status", "running");
+
+        let result = self
+            .db
+            .insert(Insert::from(insert).returning(vec!["id"]))
+            .await?;
+
+        let id = result
+            .first()
+            .and_then(|row| row["id"].as_i64())
+            .ok_or(Error::MissingId("run"))?;
+
+        *self.run.lock().unwrap() = Some(id);
+
+        Ok(id)
+    }
+
+    pub async fn finish_run(&self, status: RunStatus) -> crate::Result<()> {
+        if let Some(id) = self.current_run() {
+            let update = Update::table("run")
+                .set("finished_at", Utc::now())
+                .set("status", status.as_str())
+                .so_that("id".equals(id));
+
+            self.db.update(update).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn contains(&self, info: &ServerInfo) -> crate::Result<bool> {
         let query = Select::from_table("version")
             .so_that("commit_id".equals(info.commit.as_str()))
@@ -28,18 +90,19 @@ impl MetricsStorage {
         Ok(result)
     }
 
-    pub async fn store(&self, metrics: &ResponseTime) -> crate::Result<()> {
+    /// The id of the given version, inserted if not yet stored.
+    async fn version_id(&self, commit: &str, version: &str, connector: &str) -> crate::Result<i64> {
         let previous_version = Select::from_table("version")
-            .so_that("commit_id".equals(metrics.commit()))
-            .and_where("connector".equals(metrics.connector()));
+            .so_that("commit_id".equals(commit))
+            .and_where("connector".equals(connector));
 
         let version = match self.db.select(previous_version).await?.first() {
             Some(result) => result["id"].as_i64().unwrap(),
             None => {
                 let insert_single = Insert::single_into("version")
-                    .value("commit_id", metrics.commit())
-                    .value("version", metrics.version())
-                    .value("connector", metrics.connector());
+                    .value("commit_id", commit)
+                    .value("version", version)
+                    .value("connector", connector);
 
                 let result = self
                     .db
@@ -53,19 +116,113 @@ impl MetricsStorage {
             }
         };
 
-        let insert = Insert::single_into("response_time")
+        Ok(version)
+    }
+
+    pub async fn store(&self, metrics: &ResponseTime) -> crate::Result<()> {
+        let version = self
+            .version_id(metrics.commit(), metrics.version(), metrics.connector())
+            .await?;
+
+        let mut insert = Insert::single_into("response_time")
             .value("version", version)
             .value("time", metrics.time())
             .value("failures", metrics.failures() as i64)
             .value("p50", metrics.p50() as i64)
             .value("p95", metrics.p95() as i64)
             .value("p99", metrics.p99() as i64)
+            .value("p90", metrics.p90() as i64)
+            .value("p999", metrics.p999() as i64)
+            .value("min", metrics.min() as i64)
+            .value("max", metrics.max() as i64)
+            .value("mean", metrics.mean())
+            .value("stddev", metrics.stddev())
             .value("query_name", metrics.query_name())
             .value("rps", metrics.rps() as i64)
             .value("successes", metrics.successes() as i64);
 
+        if let Some(histogram) = metrics.histogram() {
+            insert = insert.value("histogram", histogram);
+        }
+
+        if let Some(endpoint) = metrics.endpoint() {
+            insert = insert.value("endpoint", endpoint);
+        }
+
+        if let Some(batch) = metrics.batch() {
+            insert = insert.value("batch", batch as i64);
+        }
+
+        if let Some(run) = self.current_run() {
+            insert = insert.value("run", run);
+        }
+
         self.db.insert(insert.into()).await?;
 
         Ok(())
     }
+
+    pub async fn store_series(&self, series: &[SeriesPoint]) -> crate::Result<()> {
+        let first = match series.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+
+        let version = self
+            .version_id(first.commit(), first.version(), first.connector())
+            .await?;
+
+        for point in series {
+            let mut insert = Insert::single_into("response_time_series")
+                .value("version", version)
+                .value("time", point.time())
+                .value("second", point.second() as i64)
+                .value("query_name", point.query_name())
+                .value("rps", point.rps() as i64)
+                .value("sent", point.sent() as i64)
+                .value("completed", point.completed() as i64)
+                .value("errors", point.errors() as i64)
+                .value("p50", point.p50() as i64)
+                .value("p99", point.p99() as i64);
+
+            if let Some(endpoint) = point.endpoint() {
+                insert = insert.value("endpoint", endpoint);
+            }
+
+            if let Some(batch) = point.batch() {
+                insert = insert.value("batch", batch as i64);
+            }
+
+            if let Some(run) = self.current_run() {
+                insert = insert.value("run", run);
+            }
+
+            self.db.insert(insert.into()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MetricsSink for MetricsStorage {
+    async fn contains(&self, info: &ServerInfo) -> crate::Result<bool> {
+        MetricsStorage::contains(self, info).await
+    }
+
+    async fn start_run(&self, run: &RunInfo) -> crate::Result<Option<i64>> {
+        MetricsStorage::start_run(self, run).await.map(Some)
+    }
+
+    async fn finish_run(&self, status: RunStatus) -> crate::Result<()> {
+        MetricsStorage::finish_run(self, status).await
+    }
+
+    async fn store(&self, metrics: &ResponseTime) -> crate::Result<()> {
+        MetricsStorage::store(self, metrics).await
+    }
+
+    async fn store_series(&self, series: &[SeriesPoint]) -> crate::Result<()> {
+        MetricsStorage::store_series(self, series).await
+    }
 }
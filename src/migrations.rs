@@ -0,0 +1,278 @@
+//! Versioned schema of the secondary storage. Every migration is applied
+//! once and recorded in `chihiro_migrations`, so new columns reach existing
+//! databases on the next run.
+//!
+//! The statements use placeholders for the types differing between SQLite,
+//! PostgreSQL and MySQL. Tables are created only if missing and columns
+//! added only if not there yet, so databases set up by hand from
+//! `benchmark_results.prisma` are upgraded in place too.
+
+use crate::error::Error;
+use chrono::Utc;
+use quaint::{prelude::*, single::Quaint};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Family {
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+impl Family {
+    fn from_url(url: &str) -> crate::Result<Self> {
+        let scheme = url.split(':').next().unwrap_or_default();
+
+        match scheme {
+            "file" | "sqlite" => Ok(Self::Sqlite),
+            "postgres" | "postgresql" => Ok(Self::Postgres),
+            "mysql" => Ok(Self::Mysql),
+            _ => Err(Error::InvalidDatabaseType(scheme.into())),
+        }
+    }
+
+    /// Replaces the type placeholders of the statement.
+    fn sql(self, statement: &str) -> String {
+        let types: &[(&str, &str)] = match self {
+            Self::Sqlite => &[
+                ("{id}", "INTEGER PRIMARY KEY AUTOINCREMENT"),
+                ("{int}", "INTEGER"),
+                ("{bigint}", "INTEGER"),
+                ("{float}", "REAL"),
+                ("{string}", "TEXT"),
+                ("{text}", "TEXT"),
+                ("{time}", "DATETIME"),
+            ],
+            Self::Postgres => &[
+                ("{id}", "SERIAL PRIMARY KEY"),
+                ("{int}", "INTEGER"),
+                ("{bigint}", "BIGINT"),
+                ("{float}", "DOUBLE PRECISION"),
+                ("{string}", "TEXT"),
+                ("{text}", "TEXT"),
+                ("{time}", "TIMESTAMP(3)"),
+            ],
+            Self::Mysql => &[
+                ("{id}", "INTEGER AUTO_INCREMENT PRIMARY KEY"),
+                ("{int}", "INTEGER"),
+                ("{bigint}", "BIGINT"),
+                ("{float}", "DOUBLE"),
+                ("{string}", "VARCHAR(191)"),
+                ("{text}", "MEDIUMTEXT"),
+                ("{time}", "DATETIME(3)"),
+            ],
+        };
+
+        types
+            .iter()
+            .fold(statement.to_string(), |acc, (placeholder, typ)| {
+                acc.replace(placeholder, typ)
+            })
+    }
+}
+
+enum Step {
+    /// A `CREATE TABLE IF NOT EXISTS` statement.
+    CreateTable(&'static str),
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+}
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    steps: &'static [Step],
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "versions and response times",
+        steps: &[
+            Step::CreateTable(
+                "CREATE TABLE IF NOT EXISTS version (
+                    id {id},
+                    commit_id {string} NOT NULL,
+                    connector {string} NOT NULL,
+                    version {string} NOT NULL
+                )",
+            ),
+            Step::CreateTable(
+                "CREATE TABLE IF NOT EXISTS response_time (
+                    id {id},
+                    failures {bigint} NOT NULL,
+                    p50 {bigint} NOT NULL,
+                    p95 {bigint} NOT NULL,
+                    p99 {bigint} NOT NULL,
+                    query_name {string} NOT NULL,
+                    rps {int} NOT NULL,
+                    successes {bigint} NOT NULL,
+                    time {time} NOT NULL,
+                    version {int} NOT NULL REFERENCES version(id)
+                )",
+            ),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "endpoint and batch size",
+        steps: &[
+            Step::AddColumn {
+                table: "response_time",
+                column: "endpoint",
+                definition: "{string}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "batch",
+                definition: "{int}",
+            },
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "full histograms and statistics",
+        steps: &[
+            Step::AddColumn {
+                table: "response_time",
+                column: "p90",
+                definition: "{bigint}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "p999",
+                definition: "{bigint}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "min",
+                definition: "{bigint}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "max",
+                definition: "{bigint}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "mean",
+                definition: "{float}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "stddev",
+                definition: "{float}",
+            },
+            Step::AddColumn {
+                table: "response_time",
+                column: "histogram",
+                definition: "{text}",
+            },
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "per-second time series",
+        steps: &[Step::CreateTable(
+            "CREATE TABLE IF NOT EXISTS response_time_series (
+                id {id},
+                query_name {string} NOT NULL,
+                rps {int} NOT NULL,
+                second {int} NOT NULL,
+                sent {bigint} NOT NULL,
+                completed {bigint} NOT NULL,
+                errors {bigint} NOT NULL,
+                p50 {bigint} NOT NULL,
+                p99 {bigint} NOT NULL,
+                time {time} NOT NULL,
+                version {int} NOT NULL REFERENCES version(id),
+                endpoint {string},
+                batch {int}
+            )",
+        )],
+    },
+    Migration {
+        version: 5,
+        description: "benchmark runs",
+        steps: &[
+            Step::CreateTable(
+                "CREATE TABLE IF NOT EXISTS run (
+                    id {id},
+                    identifier {string} NOT NULL,
+                    config {text} NOT NULL,
+                    endpoint_url {text} NOT NULL,
+                    endpoint_type {string} NOT NULL,
+                    host {string} NOT NULL,
+                    duration {int} NOT NULL,
+                    started_at {time} NOT NULL,
+                    finished_at {time},
+                    status {string} NOT NULL
+                )",
+            ),
+            Step::AddColumn {
+                table: "response_time",
+                column: "run",
+                definition: "{int} REFERENCES run(id)",
+            },
+            Step::AddColumn {
+                table: "response_time_series",
+                column: "run",
+                definition: "{int} REFERENCES run(id)",
+            },
+        ],
+    },
+];
+
+/// Brings the schema of the database up to date.
+pub async fn run(db: &Quaint, url: &str) -> crate::Result<()> {
+    let family = Family::from_url(url)?;
+
+    db.raw_cmd(&family.sql(
+        "CREATE TABLE IF NOT EXISTS chihiro_migrations (
+            version {int} PRIMARY KEY,
+            description {string} NOT NULL,
+            applied_at {time} NOT NULL
+        )",
+    ))
+    .await?;
+
+    let applied: Vec<i64> = db
+        .select(Select::from_table("chihiro_migrations").column("version"))
+        .await?
+        .into_iter()
+        .filter_map(|row| row["version"].as_i64())
+        .collect();
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        for step in migration.steps {
+            match step {
+                Step::CreateTable(statement) => db.raw_cmd(&family.sql(statement)).await?,
+                Step::AddColumn {
+                    table,
+                    column,
+                    definition,
+                } => {
+                    let probe = format!("SELECT {} FROM {} WHERE 1 = 0", column, table);
+
+                    if db.query_raw(&probe, &[]).await.is_err() {
+                        let statement =
+                            format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
+
+                        db.raw_cmd(&family.sql(&statement)).await?;
+                    }
+                }
+            }
+        }
+
+        let insert = Insert::single_into("chihiro_migrations")
+            .value("version", migration.version)
+            .value("description", migration.description)
+            .value("applied_at", Utc::now());
+
+        db.insert(insert.into()).await?;
+    }
+
+    Ok(())
+}
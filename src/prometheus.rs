@@ -0,0 +1,213 @@
+use http::header::CONTENT_TYPE;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Response,
+};
+use metrics_core::{Key, Observe, Observer};
+use metrics_runtime::Controller;
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+/// The labels of what a requester is currently measuring, shared between
+/// the requester and the exposition endpoint.
+#[derive(Clone, Default)]
+pub struct LiveLabels {
+    labels: Arc<RwLock<BTreeMap<&'static str, String>>>,
+}
+
+impl LiveLabels {
+    pub fn set(&self, name: &'static str, value: impl Into<String>) {
+        self.labels.write().unwrap().insert(name, value.into());
+    }
+
+    fn render(&self) -> String {
+        self.labels
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// The metrics of one requester to expose.
+pub struct Target {
+    pub controller: Arc<RwLock<Controller>>,
+    pub labels: LiveLabels,
+}
+
+enum Kind {
+    Counter,
+    Gauge,
+    Summary,
+}
+
+/// Renders the receiver contents in the Prometheus text format. Every
+/// sample gets the current labels of its requester.
+struct PrometheusObserver {
+    labels: String,
+    metrics: BTreeMap<String, (Kind, Vec<String>)>,
+}
+
+impl PrometheusObserver {
+    fn new() -> Self {
+        Self {
+            labels: String::new(),
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    fn sample(&mut self, name: &str, kind: Kind, extra_label: Option<String>, value: String) {
+        let labels = match extra_label {
+            Some(extra) if self.labels.is_empty() => extra,
+            Some(extra) => format!("{},{}", extra, self.labels),
+            None => self.labels.clone(),
+        };
+
+        let line = format!("{}{{{}}} {}", name, labels, value);
+
+        self.metrics
+            .entry(name.into())
+            .or_insert_with(|| (kind, Vec::new()))
+            .1
+            .push(line);
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+
+        for (name, (kind, lines)) in self.metrics.iter() {
+            let typ = match kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+                Kind::Summary => "summary",
+            };
+
+            output.push_str(&format!("# TYPE {} {}\n", name, typ));
+
+            for line in lines {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+
+        output
+    }
+}
+
+impl Observer for PrometheusObserver {
+    fn observe_counter(&mut self, key: Key, value: u64) {
+        let outcome = match key.name().as_ref() {
+            "success" => "success",
+            "error" => "error",
+            _ => return,
+        };
+
+        self.sample(
+            "chihiro_requests_total",
+            Kind::Counter,
+            Some(format!("outcome=\"{}\"", outcome)),
+            value.to_string(),
+        );
+    }
+
+    fn observe_gauge(&mut self, key: Key, value: i64) {
+        let name = match key.name().as_ref() {
+            "target_rps" => "chihiro_target_rps",
+            "in_flight" => "chihiro_in_flight_requests",
+            _ => return,
+        };
+
+        self.sample(name, Kind::Gauge, None, value.to_string());
+    }
+
+    fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+        if key.name().as_ref() != "response_time" || values.is_empty() {
+            return;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort();
+
+        let name = "chihiro_response_time_seconds";
+
+        for quantile in &[0.5, 0.9, 0.95, 0.99] {
+            let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+
+            self.sample(
+                name,
+                Kind::Summary,
+                Some(format!("quantile=\"{}\"", quantile)),
+                seconds(sorted[index]).to_string(),
+            );
+        }
+
+        let sum: u64 = sorted.iter().sum();
+        let labels = self.labels.clone();
+
+        let summary = &mut self.metrics.get_mut(name).unwrap().1;
+        summary.push(format!("{}_sum{{{}}} {}", name, labels, seconds(sum)));
+        summary.push(format!("{}_count{{{}}} {}", name, labels, sorted.len()));
+    }
+}
+
+fn seconds(nanos: u64) -> f64 {
+    nanos as f64 / 1_000_000_000.0
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render(targets: &[Target]) -> String {
+    let mut observer = PrometheusObserver::new();
+
+    for target in targets {
+        observer.labels = target.labels.render();
+        target.controller.read().unwrap().observe(&mut observer);
+    }
+
+    observer.render()
+}
+
+/// Serves the live metrics of the requesters in the background, for as long
+/// as the benchmark runs.
+pub fn serve(addr: SocketAddr, targets: Vec<Target>) -> crate::Result<()> {
+    let targets = Arc::new(targets);
+
+    let make_service = make_service_fn(move |_| {
+        let targets = targets.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |_| {
+                let body = render(&targets);
+
+                async move {
+                    Response::builder()
+                        .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                        .body(Body::from(body))
+                }
+            }))
+        }
+    });
+
+    let server = hyper::Server::try_bind(&addr)?.serve(make_service);
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            eprintln!("Metrics endpoint failed: {}", e);
+        }
+    });
+
+    println!("Serving live metrics at http://{}/metrics", addr);
+
+    Ok(())
+}
@@ -1,13 +1,35 @@
+mod batch;
+mod junit;
+mod markdown;
 mod slack;
 mod stdout;
 
+pub use batch::BatchReporter;
+pub use junit::JUnitReporter;
+pub use markdown::MarkdownReporter;
 pub use slack::SlackReporter;
 pub use stdout::StdoutReporter;
 
-use crate::response_summary::ConnectorType;
+use crate::response_summary::{ConnectorType, Selection};
 use async_trait::async_trait;
 
+/// Escapes text for XML and HTML, both as content and in quoted
+/// attributes.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 #[async_trait]
 pub trait Reporter {
-    async fn report(&self, path: &str, connector: ConnectorType) -> crate::Result<()>;
+    /// Reports on the selected measurements of the connector.
+    async fn report(
+        &self,
+        path: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()>;
 }
@@ -0,0 +1,55 @@
+use super::Reporter;
+use crate::response_summary::{BatchSummary, ConnectorType, Selection};
+use async_trait::async_trait;
+use console::style;
+
+/// Prints the response times per batch size of the latest measurement, both
+/// for the whole request and per query in the batch.
+pub struct BatchReporter;
+
+impl BatchReporter {
+    fn millis(nanos: f64) -> String {
+        format!("{:.3}", nanos / 1_000_000.0)
+    }
+}
+
+#[async_trait]
+impl Reporter for BatchReporter {
+    async fn report(
+        &self,
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()> {
+        let summary = BatchSummary::aggregate(url, connector, selection).await?;
+
+        println!("Batch sizes of commit {} (times in ms)", summary.commit());
+
+        for (query, averages) in summary.queries() {
+            println!();
+            println!("{}", style(query).bold());
+            println!(
+                "{:>8} {:>10} {:>10} {:>10} {:>12} {:>12} {:>12}",
+                "batch", "p50", "p95", "p99", "p50/item", "p95/item", "p99/item"
+            );
+
+            for average in averages {
+                let (p50, p95, p99) = average.percentiles();
+                let (item_p50, item_p95, item_p99) = average.per_item();
+
+                println!(
+                    "{:>8} {:>10} {:>10} {:>10} {:>12} {:>12} {:>12}",
+                    average.batch(),
+                    Self::millis(p50),
+                    Self::millis(p95),
+                    Self::millis(p99),
+                    Self::millis(item_p50),
+                    Self::millis(item_p95),
+                    Self::millis(item_p99),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
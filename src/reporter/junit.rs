@@ -0,0 +1,169 @@
+use super::{escape, Reporter};
+use crate::{
+    check::Budget,
+    response_summary::{ConnectorType, ResponseSummary, Selection},
+    significance::Change,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{fmt::Write, fs, path::PathBuf};
+
+/// Writes JUnit XML with a test case per query and rate, failing the ones
+/// that regressed beyond their budget, so CI servers keep a history per
+/// query.
+pub struct JUnitReporter {
+    output: Option<PathBuf>,
+    budget: Budget,
+}
+
+impl JUnitReporter {
+    /// Writes to `output`, or to stdout without one.
+    pub fn new(output: Option<PathBuf>, budget: Budget) -> Self {
+        Self { output, budget }
+    }
+
+    fn properties(indent: &str, properties: &[(&str, String)]) -> String {
+        let mut xml = format!("{}<properties>\n", indent);
+
+        for (name, value) in properties {
+            writeln!(
+                xml,
+                "{}  <property name=\"{}\" value=\"{}\"/>",
+                indent,
+                name,
+                escape(value)
+            )
+            .unwrap();
+        }
+
+        writeln!(xml, "{}</properties>", indent).unwrap();
+        xml
+    }
+
+    fn describe(percentile: &str, change: &Change, limit: Option<f64>) -> String {
+        let limit = match limit {
+            Some(limit) => format!("allowed {:+.2}%", limit),
+            None => String::from("no regression allowed"),
+        };
+
+        format!(
+            "{} regressed by {:+.2}% [{:+.1}, {:+.1}], {}",
+            percentile, change.change, change.low, change.high, limit
+        )
+    }
+
+    fn render(&self, summary: &ResponseSummary, connector: ConnectorType) -> String {
+        let (previous_id, next_id) = summary.commits();
+        let suite = format!("chihiro.{}", connector.as_str());
+
+        let mut cases = String::new();
+        let mut tests = 0;
+        let mut failures = 0;
+        let mut skipped = 0;
+
+        for (samples, comparison) in summary.differences() {
+            let (p50, p95, p99) = samples.mean();
+            tests += 1;
+
+            writeln!(
+                cases,
+                "    <testcase name=\"{}\" classname=\"{}.{}\" time=\"0\">",
+                escape(&samples.label()),
+                suite,
+                escape(samples.query_name())
+            )
+            .unwrap();
+
+            let mut properties = vec![
+                ("p50", format!("{:.3}", p50 / 1_000_000.0)),
+                ("p95", format!("{:.3}", p95 / 1_000_000.0)),
+                ("p99", format!("{:.3}", p99 / 1_000_000.0)),
+            ];
+
+            if let Some(ref comparison) = comparison {
+                properties.extend(vec![
+                    ("p50_change", format!("{:.2}", comparison.p50.change)),
+                    ("p95_change", format!("{:.2}", comparison.p95.change)),
+                    ("p99_change", format!("{:.2}", comparison.p99.change)),
+                    ("verdict", comparison.verdict.as_str().to_string()),
+                ]);
+            }
+
+            cases.push_str(&Self::properties("      ", &properties));
+
+            match comparison {
+                Some(comparison) => {
+                    let violations = self
+                        .budget
+                        .limits(samples.query_name())
+                        .violations(&comparison);
+
+                    if !violations.is_empty() {
+                        failures += 1;
+
+                        let messages: Vec<String> = violations
+                            .iter()
+                            .map(|v| Self::describe(v.percentile, &v.change, v.limit))
+                            .collect();
+
+                        writeln!(
+                            cases,
+                            "      <failure type=\"regression\" message=\"{}\">{}</failure>",
+                            escape(&messages.join("; ")),
+                            escape(&messages.join("\n"))
+                        )
+                        .unwrap();
+                    }
+                }
+                None => {
+                    skipped += 1;
+                    cases.push_str("      <skipped message=\"not measured in the base\"/>\n");
+                }
+            }
+
+            cases.push_str("    </testcase>\n");
+        }
+
+        let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+        writeln!(
+            report,
+            "<testsuites name=\"chihiro\" tests=\"{tests}\" failures=\"{failures}\">\n  <testsuite name=\"{suite}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" skipped=\"{skipped}\" timestamp=\"{timestamp}\">",
+            tests = tests,
+            failures = failures,
+            skipped = skipped,
+            suite = suite,
+            timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%S")
+        )
+        .unwrap();
+
+        report.push_str(&Self::properties(
+            "    ",
+            &[("base", previous_id.into()), ("head", next_id.into())],
+        ));
+        report.push_str(&cases);
+        report.push_str("  </testsuite>\n</testsuites>\n");
+
+        report
+    }
+}
+
+#[async_trait]
+impl Reporter for JUnitReporter {
+    async fn report(
+        &self,
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()> {
+        let summary = ResponseSummary::aggregate(url, connector, selection).await?;
+        let report = self.render(&summary, connector);
+
+        match self.output {
+            Some(ref path) => fs::write(path, report)?,
+            None => print!("{}", report),
+        }
+
+        Ok(())
+    }
+}
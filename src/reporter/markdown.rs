@@ -0,0 +1,153 @@
+use super::Reporter;
+use crate::{
+    response_summary::{
+        ConnectorType, QueryComparison, ResponseSamples, ResponseSummary, Selection,
+    },
+    significance::Verdict,
+};
+use async_trait::async_trait;
+use std::{fmt::Write, fs, path::PathBuf};
+
+/// Writes a GitHub flavored markdown table of the comparison, to paste as a
+/// pull request comment. Queries without a significant change are folded
+/// away.
+pub struct MarkdownReporter {
+    output: Option<PathBuf>,
+}
+
+impl MarkdownReporter {
+    /// Writes to `output`, or to stdout without one.
+    pub fn new(output: Option<PathBuf>) -> Self {
+        Self { output }
+    }
+
+    fn emoji(verdict: Verdict) -> &'static str {
+        match verdict {
+            Verdict::Improved => ":white_check_mark: improved",
+            Verdict::Regressed => ":x: regressed",
+            Verdict::Inconclusive => ":heavy_minus_sign: unchanged",
+        }
+    }
+
+    fn millis(nanos: f64) -> String {
+        format!("{:.3}", nanos / 1_000_000.0)
+    }
+
+    fn header() -> &'static str {
+        "| Query | p50 (ms) | p95 (ms) | p99 (ms) | Δ p50 / p95 / p99 | Verdict |\n\
+         |:------|---------:|---------:|---------:|------------------:|:--------|\n"
+    }
+
+    fn row(
+        previous: &ResponseSamples,
+        next: &ResponseSamples,
+        comparison: &QueryComparison,
+    ) -> String {
+        let (old_p50, old_p95, old_p99) = previous.mean();
+        let (new_p50, new_p95, new_p99) = next.mean();
+
+        let cell = |old: f64, new: f64| format!("{} → {}", Self::millis(old), Self::millis(new));
+
+        format!(
+            "| `{}` | {} | {} | {} | {:+.2}% / {:+.2}% / {:+.2}% | {} |\n",
+            next.label(),
+            cell(old_p50, new_p50),
+            cell(old_p95, new_p95),
+            cell(old_p99, new_p99),
+            comparison.p50.change,
+            comparison.p95.change,
+            comparison.p99.change,
+            Self::emoji(comparison.verdict),
+        )
+    }
+
+    fn new_row(next: &ResponseSamples) -> String {
+        let (p50, p95, p99) = next.mean();
+
+        format!(
+            "| `{}` | {} | {} | {} | | :new: new |\n",
+            next.label(),
+            Self::millis(p50),
+            Self::millis(p95),
+            Self::millis(p99),
+        )
+    }
+
+    fn render(summary: &ResponseSummary, connector: ConnectorType) -> String {
+        let (previous_id, next_id) = summary.commits();
+
+        let mut changed = String::new();
+        let mut unchanged = String::new();
+        let mut unchanged_count = 0;
+
+        for (next, comparison) in summary.differences() {
+            match (summary.previous(next), comparison) {
+                (Some(previous), Some(comparison)) => {
+                    let row = Self::row(previous, next, &comparison);
+
+                    if comparison.verdict == Verdict::Inconclusive {
+                        unchanged.push_str(&row);
+                        unchanged_count += 1;
+                    } else {
+                        changed.push_str(&row);
+                    }
+                }
+                _ => changed.push_str(&Self::new_row(next)),
+            }
+        }
+
+        let mut report = String::new();
+
+        writeln!(
+            report,
+            "### Benchmark results for {}\n\nComparing `{}` (base) to `{}` (head).\n",
+            connector.as_str(),
+            previous_id,
+            next_id
+        )
+        .unwrap();
+
+        if changed.is_empty() {
+            report.push_str("No significant changes.\n");
+        } else {
+            report.push_str(Self::header());
+            report.push_str(&changed);
+        }
+
+        if unchanged_count > 0 {
+            writeln!(
+                report,
+                "\n<details>\n<summary>{} unchanged quer{}</summary>\n",
+                unchanged_count,
+                if unchanged_count == 1 { "y" } else { "ies" }
+            )
+            .unwrap();
+
+            report.push_str(Self::header());
+            report.push_str(&unchanged);
+            report.push_str("\n</details>\n");
+        }
+
+        report
+    }
+}
+
+#[async_trait]
+impl Reporter for MarkdownReporter {
+    async fn report(
+        &self,
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()> {
+        let summary = ResponseSummary::aggregate(url, connector, selection).await?;
+        let report = Self::render(&summary, connector);
+
+        match self.output {
+            Some(ref path) => fs::write(path, report)?,
+            None => print!("{}", report),
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,16 @@
 use super::Reporter;
-use crate::response_summary::{ConnectorType, ResponseSummary};
+use crate::{
+    response_summary::{ConnectorType, QueryComparison, ResponseSummary, Selection},
+    significance::{Change, Verdict},
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::{json, Value};
+use std::io::{Error, ErrorKind};
+
+/// The most blocks Slack takes in one message. Longer reports are split
+/// into several messages.
+const MAX_BLOCKS: usize = 50;
 
 pub struct SlackReporter {
     webhook_url: String,
@@ -17,33 +25,38 @@ impl SlackReporter {
         }
     }
 
-    fn format_number(num: f64) -> String {
-        if num < 0.0 {
-            format!("{:.2}%", num)
+    fn format_number(change: &Change) -> String {
+        let interval = if change.has_interval() {
+            format!(" _[{:+.1}, {:+.1}]_", change.low, change.high)
         } else {
-            format!("*_+{:.2}%_*", num)
+            String::new()
+        };
+
+        match change.verdict {
+            Verdict::Regressed => format!("*_{:+.2}%_*{}", change.change, interval),
+            _ => format!("{:+.2}%{}", change.change, interval),
         }
     }
 
-    fn format_title(num: f64, text: &str) -> String {
-        if num < 0.0 {
-            format!(":heavy_check_mark:*{}*", text)
-        } else {
-            format!(":x:*{}*", text)
+    fn format_title(verdict: Verdict, text: &str) -> String {
+        match verdict {
+            Verdict::Improved => format!(":heavy_check_mark:*{}*", text),
+            Verdict::Regressed => format!(":x:*{}*", text),
+            Verdict::Inconclusive => format!(":grey_question:*{}*", text),
         }
     }
 
-    fn blocks_with_data(title: String, p50: f64, p95: f64, _: f64) -> serde_json::Value {
-        let p50_title = Self::format_title(p50, "p50");
-        let p95_title = Self::format_title(p95, "p95");
+    fn blocks_with_data(title: String, comparison: &QueryComparison) -> serde_json::Value {
+        let p50_title = Self::format_title(comparison.p50.verdict, "p50");
+        let p95_title = Self::format_title(comparison.p95.verdict, "p95");
 
-        let p50 = Self::format_number(p50);
-        let p95 = Self::format_number(p95);
+        let p50 = Self::format_number(&comparison.p50);
+        let p95 = Self::format_number(&comparison.p95);
 
         json!({
             "type": "section",
             "text": {
-                "text": title,
+                "text": format!("{} · *{}*", title, comparison.verdict.as_str()),
                 "type": "mrkdwn"
             },
             "fields": [
@@ -98,15 +111,20 @@ impl SlackReporter {
 
 #[async_trait]
 impl Reporter for SlackReporter {
-    async fn report(&self, url: &str, connector: ConnectorType) -> crate::Result<()> {
-        let summary = ResponseSummary::aggregate(url, connector).await?;
+    async fn report(
+        &self,
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()> {
+        let summary = ResponseSummary::aggregate(url, connector, selection).await?;
         let (previous_id, next_id) = summary.commits();
 
         let overview = format!(
             "Benchmark results for *{}* connector, comparing commit_id `{}` against commit_id `{}`. (<https://github.com/prisma/prisma-engines/compare/{}...{}|Changelog>)",
             connector.as_str(),
-            &previous_id[0..6],
-            &next_id[0..6],
+            previous_id.chars().take(6).collect::<String>(),
+            next_id.chars().take(6).collect::<String>(),
             previous_id,
             next_id,
         );
@@ -125,16 +143,16 @@ impl Reporter for SlackReporter {
             "type": "divider"
         }));
 
-        for (query, diffs) in summary.differences() {
+        for (samples, comparison) in summary.differences() {
             let title = format!(
                 "Query: <https://github.com/prisma/chihiro/blob/master/queries/sql_load_test/prisma/{}.graphql|{}>",
-                query,
-                query
+                samples.query_name(),
+                samples.label()
             );
 
-            match diffs {
-                Some((p50, p95, p99)) => {
-                    blocks.push(Self::blocks_with_data(title, p50, p95, p99));
+            match comparison {
+                Some(comparison) => {
+                    blocks.push(Self::blocks_with_data(title, &comparison));
                 }
                 None => {
                     blocks.push(Self::new_blocks(title));
@@ -146,13 +164,28 @@ impl Reporter for SlackReporter {
             "type": "divider"
         }));
 
-        let payload = json!({ "blocks": Value::from(blocks) });
+        for message in blocks.chunks(MAX_BLOCKS) {
+            let payload = json!({ "blocks": Value::from(message.to_vec()) });
+
+            let response = self
+                .client
+                .post(&self.webhook_url)
+                .json(&payload)
+                .send()
+                .await?;
 
-        self.client
-            .post(&self.webhook_url)
-            .json(&payload)
-            .send()
-            .await?;
+            let status = response.status();
+
+            if !status.is_success() {
+                let body = response.text().await?;
+
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Slack rejected the report with {}: {}", status, body),
+                )
+                .into());
+            }
+        }
 
         Ok(())
     }
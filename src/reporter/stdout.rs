@@ -1,14 +1,47 @@
 use super::Reporter;
-use crate::response_summary::{ConnectorType, ResponseSummary};
+use crate::{
+    response_summary::{ConnectorType, ResponseSummary, Selection},
+    significance::{Change, Verdict},
+};
 use async_trait::async_trait;
-use console::{pad_str, style, Alignment};
+use console::{pad_str, style, Alignment, StyledObject};
 
 pub struct StdoutReporter;
 
+impl StdoutReporter {
+    fn colored<D>(verdict: Verdict, text: D) -> StyledObject<D> {
+        match verdict {
+            Verdict::Improved => style(text).green().bold(),
+            Verdict::Regressed => style(text).red().bold(),
+            Verdict::Inconclusive => style(text).dim(),
+        }
+    }
+
+    fn format_change(name: &str, change: &Change) -> String {
+        let interval = if change.has_interval() {
+            format!(" [{:+.1}, {:+.1}]", change.low, change.high)
+        } else {
+            String::new()
+        };
+
+        format!(
+            "{}: {:>10}{:<16} ",
+            name,
+            Self::colored(change.verdict, format!("{:+.2}%", change.change)),
+            interval
+        )
+    }
+}
+
 #[async_trait]
 impl Reporter for StdoutReporter {
-    async fn report(&self, url: &str, connector: ConnectorType) -> crate::Result<()> {
-        let summary = ResponseSummary::aggregate(url, connector).await?;
+    async fn report(
+        &self,
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<()> {
+        let summary = ResponseSummary::aggregate(url, connector, selection).await?;
         let (previous_id, next_id) = summary.commits();
         let padding = summary.longest_query();
 
@@ -19,32 +52,48 @@ impl Reporter for StdoutReporter {
 
         println!();
 
-        for (query, diffs) in summary.differences() {
-            print!("{} :: ", pad_str(query, padding, Alignment::Left, None));
-
-            match diffs {
-                Some((p50, p95, p99)) => {
-                    if p50 <= 0.0 {
-                        print!("p50: {:>10} ", style(format!("{:.2}%", p50)).green().bold())
-                    } else {
-                        print!("p50: {:>10} ", style(format!("{:.2}%", p50)).red().bold())
-                    }
-
-                    if p95 <= 0.0 {
-                        print!("p95: {:>10} ", style(format!("{:.2}%", p95)).green().bold())
-                    } else {
-                        print!("p95: {:>10} ", style(format!("{:.2}%", p95)).red().bold())
-                    }
-
-                    if p99 <= 0.0 {
-                        print!("p99: {:>10} ", style(format!("{:.2}%", p99)).green().bold())
-                    } else {
-                        print!("p99: {:>10} ", style(format!("{:.2}%", p99)).red().bold())
-                    }
+        for (samples, comparison) in summary.differences() {
+            print!(
+                "{} :: ",
+                pad_str(&samples.label(), padding, Alignment::Left, None)
+            );
+
+            match comparison {
+                Some(comparison) => {
+                    print!("{}", Self::format_change("p50", &comparison.p50));
+                    print!("{}", Self::format_change("p95", &comparison.p95));
+                    print!("{}", Self::format_change("p99", &comparison.p99));
+
+                    print!(
+                        "{:<13}",
+                        Self::colored(comparison.verdict, comparison.verdict.as_str())
+                    );
                 }
                 None => print!("new"),
             }
 
+            if samples.sample_count() > 1 {
+                let (mean, _, _) = samples.mean();
+                let (spread, _, _) = samples.spread();
+
+                // Without a mean to relate to, show the spread as it is.
+                let spread = if mean == 0.0 {
+                    format!("±{:.3} ms", spread / 1_000_000.0)
+                } else {
+                    format!("±{:.2}%", spread / mean * 100.0)
+                };
+
+                print!(
+                    " {}",
+                    style(format!(
+                        "(p50 {} over {} runs)",
+                        spread,
+                        samples.sample_count()
+                    ))
+                    .dim()
+                );
+            }
+
             println!()
         }
 
@@ -1,9 +1,11 @@
 use crate::{
     bar::OptionalBar,
-    config::{Query, QueryConfig, SingleQuery},
+    config::{self, EndpointConfig, Query, QueryConfig, SingleQuery},
     console_observer::ConsoleObserver,
     error::Error,
     json_observer::{JsonObserver, ResponseTime},
+    prometheus::{LiveLabels, Target},
+    time_series::{SeriesPoint, TimeSeries},
 };
 use console::style;
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
@@ -13,8 +15,12 @@ use metrics_runtime::{Controller, Receiver};
 use serde::Deserialize;
 use serde_json::json;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     str::FromStr,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, RwLock,
+    },
     time::{Duration, Instant},
 };
 use tokio::{
@@ -22,7 +28,8 @@ use tokio::{
     time::{interval, timeout},
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EndpointType {
     Prisma,
     Hasura,
@@ -35,6 +42,16 @@ impl Default for EndpointType {
     }
 }
 
+impl EndpointType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prisma => "prisma",
+            Self::Hasura => "hasura",
+            Self::Photon => "photon",
+        }
+    }
+}
+
 impl FromStr for EndpointType {
     type Err = Error;
 
@@ -49,10 +66,18 @@ impl FromStr for EndpointType {
 }
 
 pub struct Requester {
+    name: Option<String>,
     endpoint_type: EndpointType,
     endpoint_url: String,
+    headers: HashMap<String, String>,
     receiver: Receiver,
+    /// The controller of the current receiver, shared with the exposition
+    /// endpoint.
+    live: Arc<RwLock<Controller>>,
     client: Client<HttpConnector>,
+    series: TimeSeries,
+    labels: LiveLabels,
+    in_flight: Arc<AtomicI64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -69,22 +94,86 @@ enum ResponseType {
 
 impl Requester {
     pub fn new(endpoint_type: Option<EndpointType>, endpoint_url: String) -> crate::Result<Self> {
+        config::check_url(&endpoint_url)?;
+
         let builder = Client::builder();
         let client = builder.build(HttpConnector::new());
         let receiver = Receiver::builder().build()?;
+        let live = Arc::new(RwLock::new(receiver.controller()));
         let endpoint_type = endpoint_type.unwrap_or(EndpointType::Prisma);
 
         Ok(Self {
+            name: None,
             endpoint_type,
             endpoint_url,
+            headers: HashMap::new(),
             client,
             receiver,
+            live,
+            series: TimeSeries::new(),
+            labels: LiveLabels::default(),
+            in_flight: Arc::new(AtomicI64::new(0)),
         })
     }
 
-    pub async fn run(&mut self, query: &Query, rps: u64, duration: Duration, pb: &OptionalBar) {
+    /// A requester for one of the named endpoints of the configuration.
+    pub fn from_endpoint(endpoint: &EndpointConfig) -> crate::Result<Self> {
+        let mut requester = Self::new(Some(endpoint.endpoint_type()), endpoint.url().into())?;
+
+        requester.name = Some(endpoint.name().into());
+        requester.headers = endpoint.headers().clone();
+        requester.labels.set("endpoint", endpoint.name());
+
+        Ok(requester)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn endpoint_url(&self) -> &str {
+        &self.endpoint_url
+    }
+
+    pub fn endpoint_type(&self) -> EndpointType {
+        self.endpoint_type
+    }
+
+    /// Labels the live metrics with the measured server.
+    pub fn label_server(&self, info: &ServerInfo) {
+        self.labels.set("commit", info.commit.as_str());
+        self.labels
+            .set("connector", info.primary_connector.as_str());
+    }
+
+    /// The live metrics of this requester, for the exposition endpoint.
+    pub fn live_target(&self) -> Target {
+        Target {
+            controller: self.live.clone(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    pub async fn run(
+        &mut self,
+        query: &Query,
+        rps: u64,
+        batch: Option<u64>,
+        duration: Duration,
+        pb: &OptionalBar,
+    ) -> crate::Result<()> {
         let mut rate_stream = interval(Duration::from_nanos(1_000_000_000 / rps));
 
+        // Every run starts with empty metrics, so the stored results and the
+        // live metrics only hold this query and rate.
+        self.receiver = Receiver::builder().build()?;
+        *self.live.write().unwrap() = self.receiver.controller();
+        self.series = TimeSeries::new();
+
+        self.labels.set("query", query.name());
+        self.labels.set("rps", rps.to_string());
+        self.receiver.sink().update_gauge("target_rps", rps as i64);
+
         let start = Instant::now();
         let mut tick = Instant::now();
         let mut sent_total = 0;
@@ -107,21 +196,27 @@ impl Requester {
             let mut sink = self.receiver.sink();
 
             let pb = pb.clone();
-
-            let requesting = match query {
-                Query::Single(single_query) => {
-                    timeout(Duration::from_secs(10), self.request(single_query))
-                }
-                Query::Batch { query, batch } => {
-                    timeout(Duration::from_secs(10), self.batch(query, *batch))
-                }
+            let series = self.series.clone();
+            let in_flight = self.in_flight.clone();
+
+            let requesting = match batch {
+                None => timeout(Duration::from_secs(10), self.request(query.single_query())),
+                Some(batch) => timeout(
+                    Duration::from_secs(10),
+                    self.batch(query.single_query(), batch),
+                ),
             };
 
+            self.series.sent();
+            sink.update_gauge("in_flight", in_flight.fetch_add(1, Ordering::SeqCst) + 1);
+
             let jh: JoinHandle<ResponseType> = tokio::spawn(async move {
                 let start = Instant::now();
                 let res = requesting.await;
+                let end = Instant::now();
 
-                sink.record_timing("response_time", start, Instant::now());
+                sink.record_timing("response_time", start, end);
+                sink.update_gauge("in_flight", in_flight.fetch_sub(1, Ordering::SeqCst) - 1);
 
                 let metrics = Self::drain_metrics(cont);
 
@@ -133,7 +228,7 @@ impl Requester {
                     metrics,
                 ));
 
-                match res {
+                let response_type = match res {
                     Ok(Ok(res)) => {
                         if res.status().is_success() {
                             sink.counter("success").increment();
@@ -151,7 +246,16 @@ impl Requester {
                         sink.counter("error").increment();
                         ResponseType::Error(format!("{}", e))
                     }
-                }
+                };
+
+                let error = match response_type {
+                    ResponseType::Ok => false,
+                    ResponseType::Error(_) => true,
+                };
+
+                series.completed(end.duration_since(start).as_nanos() as u64, error);
+
+                response_type
             });
 
             handles.push(jh);
@@ -173,13 +277,22 @@ impl Requester {
                 println!("{}", error);
             }
         }
+
+        Ok(())
     }
 
     pub async fn validate(&self, query_config: &QueryConfig, pb: OptionalBar) -> crate::Result<()> {
         for query in query_config.queries() {
+            if !query.single_query().runs_on(self.name()) {
+                continue;
+            }
+
             let res = match query {
                 Query::Single(single_query) => self.request(single_query).await?,
-                Query::Batch { query, batch } => self.batch(query, *batch).await?,
+                Query::Batch { query, batches } => {
+                    self.batch(query, batches.first().copied().unwrap_or(1))
+                        .await?
+                }
             };
 
             pb.inc(1);
@@ -228,59 +341,99 @@ impl Requester {
         }
     }
 
-    pub async fn json_metrics(&self, query_name: &str, rps: u64) -> crate::Result<ResponseTime> {
+    pub async fn json_metrics(
+        &self,
+        query_name: &str,
+        rps: u64,
+        batch: Option<u64>,
+    ) -> crate::Result<ResponseTime> {
         let server_info = self.server_info().await?;
-        let mut observer = JsonObserver::new(server_info, query_name, rps);
+        let mut observer =
+            JsonObserver::new(server_info, query_name, rps, batch, self.name.clone());
         let cont = self.receiver.controller();
 
         cont.observe(&mut observer);
         Ok(observer.drain())
     }
 
-    pub fn request(&self, query: &SingleQuery) -> hyper::client::ResponseFuture {
+    /// The per-second buckets of the latest run.
+    pub async fn series_metrics(
+        &self,
+        query_name: &str,
+        rps: u64,
+        batch: Option<u64>,
+    ) -> crate::Result<Vec<SeriesPoint>> {
+        let server_info = self.server_info().await?;
+
+        Ok(self
+            .series
+            .points(&server_info, query_name, rps, batch, self.name()))
+    }
+
+    /// Sends a single operation outside of the measurement to the endpoint of
+    /// `target`, failing if the endpoint responds with an error.
+    pub async fn execute(
+        &self,
+        query: &str,
+        target: &SingleQuery,
+    ) -> crate::Result<serde_json::Value> {
         let json_data = json!({
-            "query": query.query().trim(),
+            "query": query.trim(),
             "variables": {}
         });
 
-        let payload = serde_json::to_string(&json_data).unwrap();
-        let content_length = format!("{}", payload.len());
+        let res = self.post(target, &json_data).await?;
+        let status = res.status();
 
-        let builder = hyper::Request::builder()
-            .uri(&self.endpoint_url)
-            .method("POST")
-            .header(CONTENT_LENGTH, &content_length)
-            .header(CONTENT_TYPE, "application/json");
+        let bytes = hyper::body::to_bytes(res.into_body()).await?;
+        let json: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
 
-        let request = builder.body(Body::from(payload)).unwrap();
+        if !status.is_success() || json["errors"] != serde_json::Value::Null {
+            return Err(Error::InvalidQuery {
+                query: query.trim().into(),
+                error: json,
+            });
+        }
 
-        self.client.request(request)
+        Ok(json)
+    }
+
+    pub fn request(&self, query: &SingleQuery) -> hyper::client::ResponseFuture {
+        self.post(query, &query.payload())
     }
 
     pub fn batch(&self, query: &SingleQuery, batch: u64) -> hyper::client::ResponseFuture {
-        let queries: Vec<serde_json::Value> = (0..batch)
-            .map(|_| {
-                json!({
-                    "query": query.query().trim(),
-                    "variables": {},
-                })
-            })
-            .collect();
+        let queries: Vec<serde_json::Value> = (0..batch).map(|_| query.payload()).collect();
 
         let json_data = json!({
             "batch": queries,
         });
 
-        let payload = serde_json::to_string(&json_data).unwrap();
+        self.post(query, &json_data)
+    }
+
+    fn post(
+        &self,
+        query: &SingleQuery,
+        json_data: &serde_json::Value,
+    ) -> hyper::client::ResponseFuture {
+        let payload = serde_json::to_string(json_data).unwrap();
         let content_length = format!("{}", payload.len());
 
-        let builder = hyper::Request::builder()
-            .uri(&self.endpoint_url)
+        let mut builder = hyper::Request::builder()
+            .uri(query.endpoint_url().unwrap_or(&self.endpoint_url))
             .method("POST")
             .header(CONTENT_LENGTH, &content_length)
             .header(CONTENT_TYPE, "application/json");
 
-        let request = builder.body(Body::from(payload)).unwrap();
+        for (name, value) in self.headers.iter().chain(query.headers()) {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        // URLs and headers are checked when loading the configuration.
+        let request = builder
+            .body(Body::from(payload))
+            .expect("invalid URL or header");
 
         self.client.request(request)
     }
@@ -1,4 +1,9 @@
-use crate::error::Error;
+use crate::{
+    error::Error,
+    histogram,
+    significance::{Change, Verdict},
+};
+use hdrhistogram::Histogram;
 use quaint::{ast::avg, prelude::*, single::Quaint};
 use serde::Deserialize;
 use std::{collections::BTreeMap, str::FromStr, string::ToString};
@@ -36,33 +41,630 @@ impl FromStr for ConnectorType {
     }
 }
 
+/// A single stored measurement of a query at one rate.
 #[derive(Debug, Deserialize)]
-pub struct ResponseAverage {
+struct ResponseRow {
     query_name: String,
+    endpoint: Option<String>,
     commit_id: String,
+    rps: i64,
+    batch: Option<i64>,
     p50: f64,
     p95: f64,
     p99: f64,
+    histogram: Option<String>,
+    successes: i64,
+    failures: i64,
 }
 
-#[derive(Debug, Default)]
-pub struct ResponseSummary {
-    previous_averages: BTreeMap<String, ResponseAverage>,
-    next_averages: BTreeMap<String, ResponseAverage>,
+/// What is compared between two measurements: a query on one endpoint at
+/// one rate and batch size.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SampleKey {
+    query_name: String,
+    endpoint: Option<String>,
+    rps: u64,
+    batch: Option<u64>,
 }
 
-impl ResponseSummary {
-    pub async fn aggregate(url: &str, connector: ConnectorType) -> crate::Result<Self> {
-        let db = Quaint::new(url).await?;
+impl SampleKey {
+    /// The query with its endpoint, rate and batch size, e.g.
+    /// `users @hasura (100 rps)`.
+    fn label(&self) -> String {
+        let query = match self.endpoint {
+            Some(ref endpoint) => format!("{} @{}", self.query_name, endpoint),
+            None => self.query_name.clone(),
+        };
+
+        match self.batch {
+            Some(batch) => format!("{} ({} rps, batch of {})", query, self.rps, batch),
+            None => format!("{} ({} rps)", query, self.rps),
+        }
+    }
+}
+
+/// All samples of a query at one rate, one per stored run.
+#[derive(Debug)]
+pub struct ResponseSamples {
+    key: SampleKey,
+    p50: Vec<f64>,
+    p95: Vec<f64>,
+    p99: Vec<f64>,
+    histograms: Vec<Histogram<u64>>,
+    successes: u64,
+    failures: u64,
+}
+
+impl ResponseSamples {
+    fn new(key: SampleKey) -> Self {
+        Self {
+            key,
+            p50: Vec::new(),
+            p95: Vec::new(),
+            p99: Vec::new(),
+            histograms: Vec::new(),
+            successes: 0,
+            failures: 0,
+        }
+    }
+
+    fn push(&mut self, row: &ResponseRow) {
+        self.p50.push(row.p50);
+        self.p95.push(row.p95);
+        self.p99.push(row.p99);
+        self.successes += row.successes as u64;
+        self.failures += row.failures as u64;
+
+        let decoded = row
+            .histogram
+            .as_ref()
+            .filter(|h| !h.is_empty())
+            .and_then(|h| histogram::decode(h).ok());
+
+        if let Some(decoded) = decoded {
+            self.histograms.push(decoded);
+        }
+    }
+
+    /// All response times of the samples, if every sample stored them.
+    fn merged_histogram(&self) -> Option<Histogram<u64>> {
+        if self.histograms.is_empty() || self.histograms.len() != self.p50.len() {
+            return None;
+        }
+
+        let mut merged = histogram::new();
+
+        for h in self.histograms.iter() {
+            merged.add(h).ok()?;
+        }
+
+        Some(merged)
+    }
+
+    pub fn query_name(&self) -> &str {
+        &self.key.query_name
+    }
+
+    pub fn endpoint(&self) -> Option<&str> {
+        self.key.endpoint.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn rps(&self) -> u64 {
+        self.key.rps
+    }
+
+    pub fn batch(&self) -> Option<u64> {
+        self.key.batch
+    }
+
+    /// The query with its endpoint, rate and batch size, e.g.
+    /// `users @hasura (100 rps)`.
+    pub fn label(&self) -> String {
+        self.key.label()
+    }
+
+    /// Failed requests in percent of all requests of the samples.
+    pub fn error_rate(&self) -> f64 {
+        match self.successes + self.failures {
+            0 => 0.0,
+            total => self.failures as f64 / total as f64 * 100.0,
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.p50.len()
+    }
+
+    /// The mean p50, p95 and p99 over all samples.
+    pub fn mean(&self) -> (f64, f64, f64) {
+        (mean(&self.p50), mean(&self.p95), mean(&self.p99))
+    }
+
+    /// The sample standard deviation of p50, p95 and p99, zero with a
+    /// single sample.
+    pub fn spread(&self) -> (f64, f64, f64) {
+        (stddev(&self.p50), stddev(&self.p95), stddev(&self.p99))
+    }
+}
+
+/// The changes of p50, p95 and p99 between two measurements of a query, and
+/// what they add up to.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryComparison {
+    pub p50: Change,
+    pub p95: Change,
+    pub p99: Change,
+    pub verdict: Verdict,
+}
+
+impl QueryComparison {
+    /// Bootstraps the mean of repeated samples. With a single sample on
+    /// either side, bootstraps the percentiles from the stored histograms
+    /// instead, and without those, only the threshold tells noise apart.
+    fn new(base: &ResponseSamples, head: &ResponseSamples, threshold: f64) -> Self {
+        let histograms = match (base.merged_histogram(), head.merged_histogram()) {
+            (Some(base), Some(head)) => Some((base, head)),
+            _ => None,
+        };
+
+        let change = |base_values: &[f64], head_values: &[f64], quantile: f64| {
+            if base_values.len() > 1 && head_values.len() > 1 {
+                Change::from_samples(base_values, head_values, threshold)
+            } else if let Some((ref base, ref head)) = histograms {
+                Change::from_histograms(base, head, quantile, threshold)
+            } else {
+                Change::without_spread(mean(base_values), mean(head_values), threshold)
+            }
+        };
+
+        let p50 = change(&base.p50, &head.p50, 0.5);
+        let p95 = change(&base.p95, &head.p95, 0.95);
+        let p99 = change(&base.p99, &head.p99, 0.99);
+        let verdict = Verdict::combine(&[p50.verdict, p95.verdict, p99.verdict]);
+
+        Self {
+            p50,
+            p95,
+            p99,
+            verdict,
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn stddev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = mean(values);
+    let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+
+    (squares / (values.len() - 1) as f64).sqrt()
+}
+
+/// Which measurements a report looks at. Every field is optional, the
+/// defaults compare the latest version of the connector to the one before.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// The measurement compared against.
+    pub base: Option<String>,
+    /// The measurement reported on.
+    pub head: Option<String>,
+    /// A run to report on, compared to the run before it.
+    pub run: Option<i64>,
+    /// Changes smaller than this, in percent, count as noise.
+    pub noise_threshold: f64,
+}
+
+/// One side of a comparison: every run of a version, or a single run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Measurement {
+    Version(i64),
+    Run(i64),
+}
+
+impl Measurement {
+    /// Finds the measurement a reference names: a run as `run:<id>`, a
+    /// commit id or its prefix, or a version name such as a release tag.
+    /// Commit prefixes can be all digits, so runs need the explicit form.
+    pub async fn resolve(
+        db: &Quaint,
+        connector: ConnectorType,
+        reference: &str,
+    ) -> crate::Result<Self> {
+        if reference.starts_with("run:") {
+            let unknown = || Error::UnknownMeasurement {
+                reference: reference.into(),
+                connector: connector.to_string(),
+            };
+
+            let id: i64 = reference["run:".len()..].parse().map_err(|_| unknown())?;
+
+            let run = Select::from_table("run")
+                .column("id")
+                .so_that("id".equals(id));
+
+            return match db.select(run).await?.first() {
+                Some(_) => Ok(Self::Run(id)),
+                None => Err(unknown()),
+            };
+        }
+
+        let by_commit = Select::from_table("version")
+            .column("id")
+            .column("commit_id")
+            .so_that("connector".equals(connector.as_str()))
+            .and_where("commit_id".begins_with(reference))
+            .order_by("id".descend());
+
+        let versions: Vec<_> = db.select(by_commit).await?.into_iter().collect();
+
+        let mut commits: Vec<String> = versions
+            .iter()
+            .filter_map(|row| row["commit_id"].as_str().map(String::from))
+            .collect();
+
+        commits.sort();
+        commits.dedup();
+
+        match commits.len() {
+            0 => (),
+            1 => {
+                let id = versions.first().and_then(|row| row["id"].as_i64()).unwrap();
+                return Ok(Self::Version(id));
+            }
+            _ => {
+                return Err(Error::AmbiguousMeasurement {
+                    reference: reference.into(),
+                    candidates: commits.join(", "),
+                })
+            }
+        }
+
+        let by_name = Select::from_table("version")
+            .column("id")
+            .so_that("connector".equals(connector.as_str()))
+            .and_where("version".equals(reference))
+            .order_by("id".descend())
+            .limit(1);
+
+        match db.select(by_name).await?.first() {
+            Some(row) => Ok(Self::Version(row["id"].as_i64().unwrap())),
+            None => Err(Error::UnknownMeasurement {
+                reference: reference.into(),
+                connector: connector.to_string(),
+            }),
+        }
+    }
 
-        let selected_versions = Select::from_table("version")
+    /// The latest version of the connector.
+    async fn latest(db: &Quaint, connector: ConnectorType) -> crate::Result<Option<Self>> {
+        let latest = Select::from_table("version")
             .column("id")
             .so_that("connector".equals(connector.as_str()))
             .order_by("id".descend())
-            .limit(2);
+            .limit(1);
+
+        Ok(db
+            .select(latest)
+            .await?
+            .first()
+            .and_then(|row| row["id"].as_i64())
+            .map(Self::Version))
+    }
+
+    /// The version or run of the connector measured before this one. For a
+    /// run, the latest earlier run of another commit, so repeated runs of
+    /// the same commit are not compared with each other.
+    async fn previous(&self, db: &Quaint, connector: ConnectorType) -> crate::Result<Option<Self>> {
+        match *self {
+            Self::Version(id) => {
+                let previous = Select::from_table("version")
+                    .column("id")
+                    .so_that("connector".equals(connector.as_str()))
+                    .and_where("id".less_than(id))
+                    .order_by("id".descend())
+                    .limit(1);
+
+                Ok(db
+                    .select(previous)
+                    .await?
+                    .first()
+                    .and_then(|row| row["id"].as_i64())
+                    .map(Self::Version))
+            }
+            Self::Run(id) => {
+                let commit =
+                    Select::from_table("response_time")
+                        .column(Column::from(("version", "commit_id")).alias("commit_id"))
+                        .inner_join("version".on(
+                            ("version", "id").equals(Column::from(("response_time", "version"))),
+                        ))
+                        .so_that(("response_time", "run").equals(id))
+                        .limit(1);
+
+                let commit = match db.select(commit).await?.first() {
+                    Some(row) => row["commit_id"].as_str().map(String::from),
+                    None => None,
+                };
+
+                let commit = match commit {
+                    Some(commit) => commit,
+                    None => return Ok(None),
+                };
+
+                let previous =
+                    Select::from_table("response_time")
+                        .column(Column::from(("response_time", "run")).alias("run"))
+                        .inner_join("version".on(
+                            ("version", "id").equals(Column::from(("response_time", "version"))),
+                        ))
+                        .so_that(("version", "connector").equals(connector.as_str()))
+                        .and_where(("response_time", "run").less_than(id))
+                        .and_where(("version", "commit_id").not_equals(commit))
+                        .order_by(("response_time", "run").descend())
+                        .limit(1);
+
+                Ok(db
+                    .select(previous)
+                    .await?
+                    .first()
+                    .and_then(|row| row["run"].as_i64())
+                    .map(Self::Run))
+            }
+        }
+    }
+
+    /// The measurement to report on.
+    pub async fn head(
+        db: &Quaint,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<Self> {
+        let head = match (&selection.head, selection.run) {
+            (Some(reference), _) => Some(Self::resolve(db, connector, reference).await?),
+            (None, Some(run)) => Some(Self::Run(run)),
+            (None, None) => Self::latest(db, connector).await?,
+        };
+
+        head.ok_or_else(|| Error::MissingComparison {
+            side: "head",
+            connector: connector.to_string(),
+        })
+    }
+
+    /// The measurement to compare the head with.
+    pub async fn base(
+        db: &Quaint,
+        connector: ConnectorType,
+        selection: &Selection,
+        head: Self,
+    ) -> crate::Result<Self> {
+        let base = match selection.base {
+            Some(ref reference) => Some(Self::resolve(db, connector, reference).await?),
+            None => head.previous(db, connector).await?,
+        };
+
+        base.ok_or_else(|| Error::MissingComparison {
+            side: "base",
+            connector: connector.to_string(),
+        })
+    }
+
+    /// Narrows a query joining `response_time` and `version` to this
+    /// measurement.
+    fn filter<'a>(self, select: Select<'a>) -> Select<'a> {
+        match self {
+            Self::Version(id) => select.and_where(("version", "id").equals(id)),
+            Self::Run(id) => select.and_where(("response_time", "run").equals(id)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ResponseSummary {
+    noise_threshold: f64,
+    previous_commit: String,
+    next_commit: String,
+    previous_samples: BTreeMap<SampleKey, ResponseSamples>,
+    next_samples: BTreeMap<SampleKey, ResponseSamples>,
+}
+
+impl ResponseSummary {
+    /// Compares the selected measurements of the connector, taking every
+    /// run of a version as a sample.
+    pub async fn aggregate(
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<Self> {
+        let db = Quaint::new(url).await?;
+
+        let head = Measurement::head(&db, connector, selection).await?;
+        let base = Measurement::base(&db, connector, selection, head).await?;
+
+        let (next_commit, next_samples) = Self::samples(&db, connector, head, "head").await?;
+        let (previous_commit, previous_samples) =
+            Self::samples(&db, connector, base, "base").await?;
 
+        Ok(Self {
+            noise_threshold: selection.noise_threshold,
+            previous_commit,
+            next_commit,
+            previous_samples,
+            next_samples,
+        })
+    }
+
+    /// The commit and the samples per query and rate of a measurement.
+    async fn samples(
+        db: &Quaint,
+        connector: ConnectorType,
+        measurement: Measurement,
+        side: &'static str,
+    ) -> crate::Result<(String, BTreeMap<SampleKey, ResponseSamples>)> {
         let select = Select::from_table("response_time")
             .column(Column::from(("response_time", "query_name")).alias("query_name"))
+            .column(Column::from(("response_time", "endpoint")).alias("endpoint"))
+            .column(Column::from(("version", "commit_id")).alias("commit_id"))
+            .column(Column::from(("response_time", "rps")).alias("rps"))
+            .column(Column::from(("response_time", "batch")).alias("batch"))
+            .column(Column::from(("response_time", "p50")).alias("p50"))
+            .column(Column::from(("response_time", "p95")).alias("p95"))
+            .column(Column::from(("response_time", "p99")).alias("p99"))
+            .column(Column::from(("response_time", "histogram")).alias("histogram"))
+            .column(Column::from(("response_time", "successes")).alias("successes"))
+            .column(Column::from(("response_time", "failures")).alias("failures"))
+            .inner_join(
+                "version".on(("version", "id").equals(Column::from(("response_time", "version")))),
+            )
+            .so_that(("version", "connector").equals(connector.as_str()));
+
+        let select = measurement.filter(select);
+        let rows: Vec<ResponseRow> = quaint::serde::from_rows(db.select(select).await?)?;
+
+        let commit = match rows.first() {
+            Some(row) => row.commit_id.clone(),
+            None => {
+                return Err(Error::MissingComparison {
+                    side,
+                    connector: connector.to_string(),
+                })
+            }
+        };
+
+        let mut samples = BTreeMap::new();
+
+        for row in rows.into_iter() {
+            let key = SampleKey {
+                query_name: row.query_name.clone(),
+                endpoint: row.endpoint.clone(),
+                rps: row.rps as u64,
+                batch: row.batch.map(|b| b as u64),
+            };
+
+            samples
+                .entry(key.clone())
+                .or_insert_with(|| ResponseSamples::new(key))
+                .push(&row);
+        }
+
+        Ok((commit, samples))
+    }
+
+    /// The comparison of every query and rate of the head with the base.
+    /// `None` for queries not measured in the base.
+    pub fn differences(&self) -> Vec<(&ResponseSamples, Option<QueryComparison>)> {
+        self.next_samples
+            .iter()
+            .map(|(key, next)| {
+                let comparison = self
+                    .previous_samples
+                    .get(key)
+                    .map(|previous| QueryComparison::new(previous, next, self.noise_threshold));
+
+                (next, comparison)
+            })
+            .collect()
+    }
+
+    /// The samples of the base for the same query and rate as `next`.
+    pub fn previous(&self, next: &ResponseSamples) -> Option<&ResponseSamples> {
+        self.previous_samples.get(&next.key)
+    }
+
+    /// All samples of the base, ordered by query, rate and batch size.
+    pub fn previous_samples(&self) -> impl Iterator<Item = &ResponseSamples> {
+        self.previous_samples.values()
+    }
+
+    /// All samples of the head, ordered by query, rate and batch size.
+    pub fn next_samples(&self) -> impl Iterator<Item = &ResponseSamples> {
+        self.next_samples.values()
+    }
+
+    pub fn longest_query(&self) -> usize {
+        self.next_samples
+            .values()
+            .map(|samples| samples.label().len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn commits(&self) -> (&str, &str) {
+        (&self.previous_commit, &self.next_commit)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchAverage {
+    query_name: String,
+    commit_id: String,
+    rps: i64,
+    endpoint: Option<String>,
+    batch: i64,
+    p50: f64,
+    p95: f64,
+    p99: f64,
+}
+
+impl BatchAverage {
+    pub fn batch(&self) -> u64 {
+        self.batch as u64
+    }
+
+    /// The query with its endpoint and rate, e.g. `users @hasura (100 rps)`.
+    fn label(&self) -> String {
+        match self.endpoint {
+            Some(ref endpoint) => format!("{} @{} ({} rps)", self.query_name, endpoint, self.rps),
+            None => format!("{} ({} rps)", self.query_name, self.rps),
+        }
+    }
+
+    /// Response time percentiles of the whole batch request.
+    pub fn percentiles(&self) -> (f64, f64, f64) {
+        (self.p50, self.p95, self.p99)
+    }
+
+    /// Response time percentiles divided by the number of queries in the
+    /// batch.
+    pub fn per_item(&self) -> (f64, f64, f64) {
+        let batch = self.batch.max(1) as f64;
+        (self.p50 / batch, self.p95 / batch, self.p99 / batch)
+    }
+}
+
+/// Response times of the latest version per query, endpoint, rate and
+/// batch size, for finding the batch size with the best per-item latency.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    commit_id: String,
+    averages: BTreeMap<(String, Option<String>, i64), Vec<BatchAverage>>,
+}
+
+impl BatchSummary {
+    /// The latest version of the connector, or the selected head.
+    pub async fn aggregate(
+        url: &str,
+        connector: ConnectorType,
+        selection: &Selection,
+    ) -> crate::Result<Self> {
+        let db = Quaint::new(url).await?;
+        let head = Measurement::head(&db, connector, selection).await?;
+
+        let select = Select::from_table("response_time")
+            .column(Column::from(("response_time", "query_name")).alias("query_name"))
+            .column(Column::from(("response_time", "rps")).alias("rps"))
+            .column(Column::from(("response_time", "endpoint")).alias("endpoint"))
+            .column(Column::from(("response_time", "batch")).alias("batch"))
             .column(Column::from(("version", "commit_id")).alias("commit_id"))
             .value(avg(("response_time", "p50")).alias("p50"))
             .value(avg(("response_time", "p95")).alias("p95"))
@@ -70,86 +672,194 @@ impl ResponseSummary {
             .inner_join(
                 "version".on(("version", "id").equals(Column::from(("response_time", "version")))),
             )
-            .so_that(("version", "id").in_selection(selected_versions))
+            .so_that(("response_time", "batch").is_not_null())
+            .and_where(("version", "connector").equals(connector.as_str()))
             .group_by(("response_time", "query_name"))
+            .group_by(("response_time", "rps"))
+            .group_by(("response_time", "endpoint"))
+            .group_by(("response_time", "batch"))
             .group_by(("version", "commit_id"))
-            .group_by(("version", "id"))
-            .order_by(("version", "id").descend())
-            .order_by(("response_time", "query_name").ascend());
+            .order_by(("response_time", "query_name").ascend())
+            .order_by(("response_time", "endpoint").ascend())
+            .order_by(("response_time", "rps").ascend())
+            .order_by(("response_time", "batch").ascend());
 
-        let times: Vec<ResponseAverage> = quaint::serde::from_rows(db.select(select).await?)?;
+        let select = head.filter(select);
+        let rows: Vec<BatchAverage> = quaint::serde::from_rows(db.select(select).await?)?;
         let mut summary = Self::default();
 
-        for time in times.into_iter() {
-            summary.insert(time);
+        for row in rows.into_iter() {
+            summary.commit_id = row.commit_id.clone();
+
+            summary
+                .averages
+                .entry((row.query_name.clone(), row.endpoint.clone(), row.rps))
+                .or_insert_with(Vec::new)
+                .push(row);
         }
 
-        if summary.next_averages.is_empty() {
+        if summary.averages.is_empty() {
             Err(Error::NotEnoughMeasurements(connector.to_string()))
         } else {
             Ok(summary)
         }
     }
 
-    pub fn differences(&self) -> Vec<(&str, Option<(f64, f64, f64)>)> {
-        self.next_averages
-            .iter()
-            .map(|(key, next)| {
-                let query_name = next.query_name.as_str();
-
-                match self.previous_averages.get(key) {
-                    Some(previous) => (
-                        query_name,
-                        Some((
-                            (1.0 - previous.p50 / next.p50) * 100.0,
-                            (1.0 - previous.p95 / next.p95) * 100.0,
-                            (1.0 - previous.p99 / next.p99) * 100.0,
-                        )),
-                    ),
-                    None => (query_name, None),
-                }
-            })
-            .collect()
+    pub fn commit(&self) -> &str {
+        &self.commit_id
     }
 
-    pub fn longest_query(&self) -> usize {
-        self.next_averages
-            .iter()
-            .max_by(|x, y| x.0.len().cmp(&y.0.len()))
-            .unwrap()
-            .0
-            .len()
+    /// The averages by batch size of every query, endpoint and rate, with
+    /// their label.
+    pub fn queries(&self) -> impl Iterator<Item = (String, &[BatchAverage])> {
+        self.averages
+            .values()
+            .map(|averages| (averages[0].label(), averages.as_slice()))
     }
+}
 
-    fn insert(&mut self, value: ResponseAverage) {
-        let key = value.query_name.clone();
+/// A single stored measurement, with the version it belongs to.
+#[derive(Debug, Deserialize)]
+struct TrendRow {
+    version: i64,
+    commit_id: String,
+    query_name: String,
+    endpoint: Option<String>,
+    rps: i64,
+    batch: Option<i64>,
+    p50: f64,
+    p95: f64,
+    p99: f64,
+}
 
-        if !self.next_averages.contains_key(&key) {
-            self.next_averages.insert(key, value);
-        } else {
-            self.previous_averages.insert(key, value);
+/// The mean p50, p95 and p99 of a query, endpoint and rate per version, oldest
+/// first. `None` for versions that did not measure it.
+#[derive(Debug)]
+pub struct QueryTrend {
+    key: SampleKey,
+    points: Vec<Option<(f64, f64, f64)>>,
+}
+
+impl QueryTrend {
+    pub fn query_name(&self) -> &str {
+        &self.key.query_name
+    }
+
+    pub fn label(&self) -> String {
+        self.key.label()
+    }
+
+    /// One point per version of the summary.
+    pub fn points(&self) -> &[Option<(f64, f64, f64)>] {
+        &self.points
+    }
+}
+
+/// Response times of the latest versions of a connector, for following a
+/// query over many commits.
+#[derive(Debug)]
+pub struct TrendSummary {
+    commits: Vec<String>,
+    trends: Vec<QueryTrend>,
+}
+
+impl TrendSummary {
+    /// Loads the last `versions` versions of the connector.
+    pub async fn aggregate(
+        url: &str,
+        connector: ConnectorType,
+        versions: usize,
+    ) -> crate::Result<Self> {
+        let db = Quaint::new(url).await?;
+
+        let latest = Select::from_table("version")
+            .column("id")
+            .so_that("connector".equals(connector.as_str()))
+            .order_by("id".descend())
+            .limit(versions);
+
+        let oldest = db
+            .select(latest)
+            .await?
+            .into_iter()
+            .filter_map(|row| row["id"].as_i64())
+            .min()
+            .ok_or_else(|| Error::NotEnoughMeasurements(connector.to_string()))?;
+
+        let select = Select::from_table("response_time")
+            .column(Column::from(("version", "id")).alias("version"))
+            .column(Column::from(("version", "commit_id")).alias("commit_id"))
+            .column(Column::from(("response_time", "query_name")).alias("query_name"))
+            .column(Column::from(("response_time", "endpoint")).alias("endpoint"))
+            .column(Column::from(("response_time", "rps")).alias("rps"))
+            .column(Column::from(("response_time", "batch")).alias("batch"))
+            .column(Column::from(("response_time", "p50")).alias("p50"))
+            .column(Column::from(("response_time", "p95")).alias("p95"))
+            .column(Column::from(("response_time", "p99")).alias("p99"))
+            .inner_join(
+                "version".on(("version", "id").equals(Column::from(("response_time", "version")))),
+            )
+            .so_that(("version", "connector").equals(connector.as_str()))
+            .and_where(("version", "id").greater_than_or_equals(oldest))
+            .order_by(("version", "id").ascend());
+
+        let rows: Vec<TrendRow> = quaint::serde::from_rows(db.select(select).await?)?;
+
+        let mut versions: Vec<i64> = Vec::new();
+        let mut commits = Vec::new();
+
+        for row in rows.iter() {
+            if versions.last() != Some(&row.version) {
+                versions.push(row.version);
+                commits.push(row.commit_id.clone());
+            }
+        }
+
+        let mut samples: BTreeMap<SampleKey, Vec<ResponseSamples>> = BTreeMap::new();
+
+        for row in rows.into_iter() {
+            let key = SampleKey {
+                query_name: row.query_name,
+                endpoint: row.endpoint,
+                rps: row.rps as u64,
+                batch: row.batch.map(|b| b as u64),
+            };
+
+            let index = versions.iter().position(|v| *v == row.version).unwrap();
+
+            let per_version = samples.entry(key.clone()).or_insert_with(|| {
+                versions
+                    .iter()
+                    .map(|_| ResponseSamples::new(key.clone()))
+                    .collect()
+            });
+
+            let version = &mut per_version[index];
+            version.p50.push(row.p50);
+            version.p95.push(row.p95);
+            version.p99.push(row.p99);
         }
+
+        let trends = samples
+            .into_iter()
+            .map(|(key, per_version)| QueryTrend {
+                key,
+                points: per_version
+                    .iter()
+                    .map(|samples| Some(samples.mean()).filter(|_| samples.sample_count() > 0))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Self { commits, trends })
     }
 
-    pub fn commits(&self) -> (&str, &str) {
-        let next = self
-            .next_averages
-            .iter()
-            .next()
-            .unwrap()
-            .1
-            .commit_id
-            .as_str();
-
-        let previous = self
-            .previous_averages
-            .iter()
-            .next()
-            .unwrap()
-            .1
-            .commit_id
-            .as_str();
+    /// The commits of the versions, oldest first.
+    pub fn commits(&self) -> &[String] {
+        &self.commits
+    }
 
-        (previous, next)
+    pub fn trends(&self) -> &[QueryTrend] {
+        &self.trends
     }
 }
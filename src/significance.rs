@@ -0,0 +1,259 @@
+//! Tells real changes in response times from noise, with bootstrapped 95%
+//! confidence intervals of the relative change.
+
+use hdrhistogram::Histogram;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// How many times the samples are resampled.
+const RESAMPLES: usize = 1000;
+
+/// Fewer resamples for histograms, as every one draws many values.
+const HISTOGRAM_RESAMPLES: usize = 200;
+
+/// The most values drawn from a histogram per resample. Keeps long runs
+/// fast, and is plenty to estimate a percentile.
+const MAX_HISTOGRAM_DRAWS: u64 = 10_000;
+
+/// The same seed every time, so a report is reproducible.
+const SEED: u64 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Improved,
+    Regressed,
+    Inconclusive,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Improved => "improved",
+            Self::Regressed => "regressed",
+            Self::Inconclusive => "inconclusive",
+        }
+    }
+
+    /// Regressed if any of the verdicts is, improved if any is and none
+    /// regressed.
+    pub fn combine(verdicts: &[Verdict]) -> Self {
+        if verdicts.contains(&Self::Regressed) {
+            Self::Regressed
+        } else if verdicts.contains(&Self::Improved) {
+            Self::Improved
+        } else {
+            Self::Inconclusive
+        }
+    }
+}
+
+/// The change from base to head in percent of the base, positive when the
+/// head is slower, with its confidence interval.
+#[derive(Debug, Clone, Copy)]
+pub struct Change {
+    pub change: f64,
+    pub low: f64,
+    pub high: f64,
+    pub verdict: Verdict,
+}
+
+impl Change {
+    fn new(change: f64, low: f64, high: f64, threshold: f64) -> Self {
+        let verdict = if low > threshold {
+            Verdict::Regressed
+        } else if high < -threshold {
+            Verdict::Improved
+        } else {
+            Verdict::Inconclusive
+        };
+
+        Self {
+            change,
+            low,
+            high,
+            verdict,
+        }
+    }
+
+    /// True if the interval says anything beyond the change itself.
+    pub fn has_interval(&self) -> bool {
+        self.low < self.high
+    }
+
+    /// A change measured once on both sides, without any idea of the noise.
+    /// Only the threshold separates it from noise.
+    pub fn without_spread(base: f64, head: f64, threshold: f64) -> Self {
+        let change = relative(base, head);
+        Self::new(change, change, change, threshold)
+    }
+
+    /// Compares repeated measurements by bootstrapping their means.
+    pub fn from_samples(base: &[f64], head: &[f64], threshold: f64) -> Self {
+        let mut rng = StdRng::seed_from_u64(SEED);
+
+        let changes = (0..RESAMPLES)
+            .map(|_| {
+                relative(
+                    resampled_mean(base, &mut rng),
+                    resampled_mean(head, &mut rng),
+                )
+            })
+            .collect();
+
+        let change = relative(mean(base), mean(head));
+        let (low, high) = interval(changes);
+
+        Self::new(change, low, high, threshold)
+    }
+
+    /// Compares single measurements by bootstrapping the quantile from
+    /// their full histograms.
+    pub fn from_histograms(
+        base: &Histogram<u64>,
+        head: &Histogram<u64>,
+        quantile: f64,
+        threshold: f64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let base_values = Cumulative::new(base);
+        let head_values = Cumulative::new(head);
+
+        let changes = (0..HISTOGRAM_RESAMPLES)
+            .map(|_| {
+                relative(
+                    base_values.resampled_quantile(quantile, &mut rng),
+                    head_values.resampled_quantile(quantile, &mut rng),
+                )
+            })
+            .collect();
+
+        let change = relative(
+            base.value_at_quantile(quantile) as f64,
+            head.value_at_quantile(quantile) as f64,
+        );
+
+        let (low, high) = interval(changes);
+
+        Self::new(change, low, high, threshold)
+    }
+}
+
+/// A step in a series of measurements, where the mean before differs from
+/// the mean after.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangePoint {
+    /// The first value after the step.
+    pub index: usize,
+    /// The change of the mean in percent of the mean before.
+    pub change: f64,
+}
+
+impl ChangePoint {
+    /// Finds the single split of the values into two segments of constant
+    /// means that leaves the least squared error. A step smaller than the
+    /// threshold, in percent, is taken for noise. Series with a value that
+    /// is not finite have no step.
+    pub fn detect(values: &[f64], threshold: f64) -> Option<Self> {
+        if values.len() < 3 || values.iter().any(|v| !v.is_finite()) {
+            return None;
+        }
+
+        let squared_error = |values: &[f64]| {
+            let mean = mean(values);
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+        };
+
+        let index = (1..values.len()).min_by(|a, b| {
+            let (a_before, a_after) = values.split_at(*a);
+            let (b_before, b_after) = values.split_at(*b);
+
+            let a = squared_error(a_before) + squared_error(a_after);
+            let b = squared_error(b_before) + squared_error(b_after);
+
+            a.total_cmp(&b)
+        })?;
+
+        let change = relative(mean(&values[..index]), mean(&values[index..]));
+
+        if change.abs() > threshold {
+            Some(Self { index, change })
+        } else {
+            None
+        }
+    }
+}
+
+/// The recorded values of a histogram with their running counts, for
+/// drawing random values from it.
+struct Cumulative {
+    values: Vec<(u64, u64)>,
+    total: u64,
+}
+
+impl Cumulative {
+    fn new(histogram: &Histogram<u64>) -> Self {
+        let mut total = 0;
+
+        let values = histogram
+            .iter_recorded()
+            .map(|v| {
+                total += v.count_at_value();
+                (v.value_iterated_to(), total)
+            })
+            .collect();
+
+        Self { values, total }
+    }
+
+    fn draw<R: Rng>(&self, rng: &mut R) -> f64 {
+        let n = rng.gen_range(0, self.total);
+
+        let index = match self.values.binary_search_by(|(_, count)| count.cmp(&n)) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+
+        self.values[index.min(self.values.len() - 1)].0 as f64
+    }
+
+    fn resampled_quantile<R: Rng>(&self, quantile: f64, rng: &mut R) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        let draws = self.total.min(MAX_HISTOGRAM_DRAWS);
+        let mut values: Vec<f64> = (0..draws).map(|_| self.draw(rng)).collect();
+        values.sort_by(f64::total_cmp);
+
+        let index = ((values.len() - 1) as f64 * quantile).round() as usize;
+        values[index]
+    }
+}
+
+fn relative(base: f64, head: f64) -> f64 {
+    if base == 0.0 {
+        0.0
+    } else {
+        (head / base - 1.0) * 100.0
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn resampled_mean<R: Rng>(values: &[f64], rng: &mut R) -> f64 {
+    let sum: f64 = (0..values.len())
+        .map(|_| values[rng.gen_range(0, values.len())])
+        .sum();
+
+    sum / values.len() as f64
+}
+
+/// The 2.5th and 97.5th percentile of the bootstrapped changes.
+fn interval(mut changes: Vec<f64>) -> (f64, f64) {
+    changes.sort_by(f64::total_cmp);
+
+    let at = |q: f64| changes[((changes.len() - 1) as f64 * q).round() as usize];
+
+    (at(0.025), at(0.975))
+}
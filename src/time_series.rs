@@ -0,0 +1,193 @@
+use crate::{histogram, requester::ServerInfo};
+use chrono::{DateTime, Utc};
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Collects per-second buckets of a single run. Cloning shares the buckets,
+/// so the spawned requests can record into the same series.
+#[derive(Clone)]
+pub struct TimeSeries {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    buckets: Arc<Mutex<Vec<Bucket>>>,
+}
+
+struct Bucket {
+    sent: u64,
+    completed: u64,
+    errors: u64,
+    /// In microseconds.
+    response_times: Histogram<u64>,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            sent: 0,
+            completed: 0,
+            errors: 0,
+            response_times: histogram::coarse(),
+        }
+    }
+}
+
+/// One second of a run, stored as its own row or document.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesPoint {
+    commit: String,
+    version: String,
+    connector: String,
+    query_name: String,
+    rps: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint: Option<String>,
+    /// Seconds from the start of the run.
+    second: u64,
+    sent: u64,
+    completed: u64,
+    errors: u64,
+    p50: u64,
+    p99: u64,
+    time: DateTime<Utc>,
+}
+
+impl SeriesPoint {
+    pub fn commit(&self) -> &str {
+        &self.commit
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn connector(&self) -> &str {
+        &self.connector
+    }
+
+    pub fn query_name(&self) -> &str {
+        &self.query_name
+    }
+
+    pub fn rps(&self) -> u64 {
+        self.rps
+    }
+
+    pub fn batch(&self) -> Option<u64> {
+        self.batch
+    }
+
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn second(&self) -> u64 {
+        self.second
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub fn p50(&self) -> u64 {
+        self.p50
+    }
+
+    pub fn p99(&self) -> u64 {
+        self.p99
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+impl TimeSeries {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            buckets: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Counts a request sent right now.
+    pub fn sent(&self) {
+        self.with_current_bucket(|bucket| bucket.sent += 1);
+    }
+
+    /// Counts a request completed right now, taking `response_time`
+    /// nanoseconds.
+    pub fn completed(&self, response_time: u64, error: bool) {
+        self.with_current_bucket(|bucket| {
+            bucket.completed += 1;
+
+            if error {
+                bucket.errors += 1;
+            }
+
+            histogram::record(&mut bucket.response_times, response_time / 1_000);
+        });
+    }
+
+    fn with_current_bucket<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Bucket),
+    {
+        let second = Instant::now().duration_since(self.started).as_secs() as usize;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        while buckets.len() <= second {
+            buckets.push(Bucket::default());
+        }
+
+        f(&mut buckets[second]);
+    }
+
+    /// The recorded seconds of the run.
+    pub fn points(
+        &self,
+        server_info: &ServerInfo,
+        query_name: &str,
+        rps: u64,
+        batch: Option<u64>,
+        endpoint: Option<&str>,
+    ) -> Vec<SeriesPoint> {
+        let buckets = self.buckets.lock().unwrap();
+
+        buckets
+            .iter()
+            .enumerate()
+            .map(|(second, bucket)| SeriesPoint {
+                commit: server_info.commit.clone(),
+                version: server_info.version.clone(),
+                connector: server_info.primary_connector.clone(),
+                query_name: query_name.into(),
+                rps,
+                batch,
+                endpoint: endpoint.map(String::from),
+                second: second as u64,
+                sent: bucket.sent,
+                completed: bucket.completed,
+                errors: bucket.errors,
+                p50: bucket.response_times.value_at_quantile(0.5) * 1_000,
+                p99: bucket.response_times.value_at_quantile(0.99) * 1_000,
+                time: self.started_at + chrono::Duration::seconds(second as i64),
+            })
+            .collect()
+    }
+}
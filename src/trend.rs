@@ -0,0 +1,117 @@
+//! Follows every query over the latest commits of a connector, drawing the
+//! percentiles as sparklines and pointing out the commit where one of them
+//! stepped.
+
+use crate::{
+    response_summary::{QueryTrend, TrendSummary},
+    significance::ChangePoint,
+    TrendOpt,
+};
+use console::style;
+
+const TICKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A sparkline of the values, scaled from their minimum to their maximum.
+/// Versions without a measurement are left blank.
+fn sparkline(values: &[Option<f64>]) -> String {
+    let present = || values.iter().filter_map(|v| *v);
+
+    let min = present().fold(f64::INFINITY, f64::min);
+    let max = present().fold(f64::NEG_INFINITY, f64::max);
+
+    values
+        .iter()
+        .map(|value| match value {
+            Some(value) if max > min => {
+                let tick = (value - min) / (max - min) * (TICKS.len() - 1) as f64;
+                TICKS[tick.round() as usize]
+            }
+            Some(_) => TICKS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+fn millis(nanos: f64) -> String {
+    format!("{:.3}", nanos / 1_000_000.0)
+}
+
+fn print_percentile(name: &str, values: &[Option<f64>], commits: &[String], threshold: f64) {
+    let measured: Vec<(usize, f64)> = values
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| v.map(|v| (i, v)))
+        .collect();
+
+    let (first, last) = match (measured.first(), measured.last()) {
+        (Some(first), Some(last)) => (first.1, last.1),
+        _ => return,
+    };
+
+    print!(
+        "  {} {}  {:>10} → {:>10} ms",
+        name,
+        sparkline(values),
+        millis(first),
+        millis(last)
+    );
+
+    let series: Vec<f64> = measured.iter().map(|(_, v)| *v).collect();
+
+    if let Some(step) = ChangePoint::detect(&series, threshold) {
+        let (index, _) = measured[step.index];
+        let commit: String = commits[index].chars().take(7).collect();
+        let text = format!("step at {} ({:+.1}%)", commit, step.change);
+
+        if step.change > 0.0 {
+            print!("  {}", style(text).red().bold());
+        } else {
+            print!("  {}", style(text).green().bold());
+        }
+    }
+
+    println!();
+}
+
+fn print_trend(trend: &QueryTrend, commits: &[String], threshold: f64) {
+    println!("{}", style(trend.label()).bold());
+
+    let percentile = |f: fn(&(f64, f64, f64)) -> f64| -> Vec<Option<f64>> {
+        trend
+            .points()
+            .iter()
+            .map(|point| point.as_ref().map(f))
+            .collect()
+    };
+
+    print_percentile("p50", &percentile(|p| p.0), commits, threshold);
+    print_percentile("p95", &percentile(|p| p.1), commits, threshold);
+    print_percentile("p99", &percentile(|p| p.2), commits, threshold);
+}
+
+pub async fn trend(opts: TrendOpt) -> crate::Result<()> {
+    let summary =
+        TrendSummary::aggregate(&opts.secondary_storage, opts.connector, opts.commits).await?;
+
+    let commits = summary.commits();
+
+    println!(
+        "Trends of {} over {} commits, from {} (oldest) to {} (latest)",
+        opts.connector.as_str(),
+        commits.len(),
+        commits.first().map(|c| c.as_str()).unwrap_or_default(),
+        commits.last().map(|c| c.as_str()).unwrap_or_default(),
+    );
+
+    let trends = summary.trends().iter().filter(|trend| match opts.query {
+        Some(ref query) => trend.query_name() == query,
+        None => true,
+    });
+
+    for trend in trends {
+        println!();
+        print_trend(trend, commits, opts.noise_threshold);
+    }
+
+    Ok(())
+}